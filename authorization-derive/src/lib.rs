@@ -8,15 +8,40 @@ use syn::{Attribute, Ident, Type, Variant};
 /// The trait will not add any authorization to the Api by default.
 #[proc_macro_derive(Authorization, attributes(pagination, filter, sort, range))]
 pub fn authorization_derive(input: TokenStream) -> TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let ast: syn::DeriveInput = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_authorization_derive(&ast)
 }
 
 /// The derive macro #[derive(Oauth2)] is used to implement the Authorization trait for a struct.\
-/// The trait will add OAuth2 authorization to the Api.
-#[proc_macro_derive(Oauth2, attributes(pagination, filter, sort, range))]
+/// The trait will add OAuth2 authorization to the Api.\
+/// The grant is selected with `#[grant(client_credentials)]` (Default) or `#[grant(device_code)]`.\
+/// The device code grant needs a `device_endpoint` field, and logs the user code with `DeviceAuthorization::log`,
+/// or gives it to the function of `#[on_user_code(function)]`.\
+/// The client authenticates with `#[auth_method(client_secret_post)]` (Default), `client_secret_basic`,
+/// `private_key_jwt` (`private_key` field, `#[signing_algorithm(RS256)]` or `ES256`, needs the `private-key-jwt` feature
+/// of reqt), `tls_client_auth` (`tls_certificate` and `tls_key` fields) or `none`,
+/// and sends the `audience` and `resource` fields if any.
+#[proc_macro_derive(
+    Oauth2,
+    attributes(
+        grant,
+        on_user_code,
+        auth_method,
+        signing_algorithm,
+        pagination,
+        filter,
+        sort,
+        range
+    )
+)]
 pub fn oauth2_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_oauth2_derive(&ast)
 }
 
@@ -24,7 +49,10 @@ pub fn oauth2_derive(input: TokenStream) -> TokenStream {
 /// The trait will add Basic authorization to the Api.
 #[proc_macro_derive(Basic, attributes(pagination, filter, sort, range))]
 pub fn basic_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_basic_derive(&ast)
 }
 
@@ -32,31 +60,92 @@ pub fn basic_derive(input: TokenStream) -> TokenStream {
 /// The trait will add Bearer authorization to the Api.
 #[proc_macro_derive(Bearer, attributes(pagination, filter, sort, range))]
 pub fn bearer_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_bearer_derive(&ast)
 }
 
 /// The derive macro #[derive(ApiKey)] is used to implement the Authorization trait for a struct.\
-/// The trait will add ApiKey authorization to the Api.
-#[proc_macro_derive(ApiKey, attributes(pagination, filter, sort, range))]
+/// The trait will add ApiKey authorization to the Api, with the `key` field.\
+/// The key is sent in the `X-API-Key` header, or in the location given by `#[api_key_header("api-key")]`,
+/// `#[api_key_query("apikey")]` or `#[api_key_cookie("session")]`.
+#[proc_macro_derive(
+    ApiKey,
+    attributes(
+        api_key_header,
+        api_key_query,
+        api_key_cookie,
+        pagination,
+        filter,
+        sort,
+        range
+    )
+)]
 pub fn apikey_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_apikey_derive(&ast)
 }
 
 /// The derive macro #[derive(OIDC)] is used to implement the Authorization trait for a struct.\
-/// The trait will add OIDC authorization to the Api.
-#[proc_macro_derive(OIDC, attributes(pagination, filter, sort, range))]
+/// The trait will add OIDC authorization to the Api.\
+/// With an `issuer` field, the endpoints are discovered from `{issuer}/.well-known/openid-configuration`,
+/// otherwise `auth_endpoint` is the token endpoint.\
+/// The grant and the client authentication are selected like the Oauth2 derive,
+/// with the `grant`, `on_user_code`, `auth_method` and `signing_algorithm` attributes.
+#[proc_macro_derive(
+    OIDC,
+    attributes(
+        grant,
+        on_user_code,
+        auth_method,
+        signing_algorithm,
+        pagination,
+        filter,
+        sort,
+        range
+    )
+)]
 pub fn oidc_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_oidc_derive(&ast)
 }
 
 /// The derive macro #[derive(Keycloak)] is used to implement the Authorization trait for a struct.\
-/// The trait will add the AuthorizationType authorization to the Api and will use the Keycloak service.
-#[proc_macro_derive(Keycloak, attributes(auth_type, pagination, filter, sort, range))]
+/// The trait will add the AuthorizationType authorization to the Api and will use the Keycloak service.\
+/// The grant is selected with `#[grant(password)]` (Default), `#[grant(client_credentials)]`,
+/// `#[grant(refresh_token)]` or `#[grant(token_exchange)]`, and `#[offline_access]` requests an offline token.\
+/// The client authenticates like the Oauth2 derive, with the `auth_method` and `signing_algorithm` attributes.\
+/// With `#[auth_type(ApiKey)]`, the token is sent like the ApiKey derive, with the `api_key_*` attributes.
+#[proc_macro_derive(
+    Keycloak,
+    attributes(
+        auth_type,
+        grant,
+        offline_access,
+        api_key_header,
+        api_key_query,
+        api_key_cookie,
+        auth_method,
+        signing_algorithm,
+        pagination,
+        filter,
+        sort,
+        range
+    )
+)]
 pub fn keycloak_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = match syn::parse(input) {
+        Ok(ast) => ast,
+        Err(e) => return e.to_compile_error().into(),
+    };
     impl_keycloak_derive(&ast)
 }
 
@@ -76,13 +165,13 @@ fn get_attribute_types(ast: &syn::DeriveInput) -> (Type, Type, Type, Type) {
                 ..
             } = attr
             {
-                let name = token.clone().into_iter().next().unwrap().to_string();
+                let name = token.clone().into_iter().next()?.to_string();
                 syn::parse_str::<syn::Type>(&name).ok()
             } else {
                 None
             }
         })
-        .unwrap_or_else(|| syn::parse_str::<syn::Type>("RequestPagination").unwrap());
+        .unwrap_or_else(|| syn::parse_quote!(RequestPagination));
     let filter = ast
         .attrs
         .iter()
@@ -93,13 +182,13 @@ fn get_attribute_types(ast: &syn::DeriveInput) -> (Type, Type, Type, Type) {
                 ..
             } = attr
             {
-                let name = token.clone().into_iter().next().unwrap().to_string();
+                let name = token.clone().into_iter().next()?.to_string();
                 syn::parse_str::<syn::Type>(&name).ok()
             } else {
                 None
             }
         })
-        .unwrap_or_else(|| syn::parse_str::<syn::Type>("FilterRule").unwrap());
+        .unwrap_or_else(|| syn::parse_quote!(FilterRule));
     let sort = ast
         .attrs
         .iter()
@@ -110,13 +199,13 @@ fn get_attribute_types(ast: &syn::DeriveInput) -> (Type, Type, Type, Type) {
                 ..
             } = attr
             {
-                let name = token.clone().into_iter().next().unwrap().to_string();
+                let name = token.clone().into_iter().next()?.to_string();
                 syn::parse_str::<syn::Type>(&name).ok()
             } else {
                 None
             }
         })
-        .unwrap_or_else(|| syn::parse_str::<syn::Type>("SortRule").unwrap());
+        .unwrap_or_else(|| syn::parse_quote!(SortRule));
     let range = ast
         .attrs
         .iter()
@@ -127,16 +216,119 @@ fn get_attribute_types(ast: &syn::DeriveInput) -> (Type, Type, Type, Type) {
                 ..
             } = attr
             {
-                let name = token.clone().into_iter().next().unwrap().to_string();
+                let name = token.clone().into_iter().next()?.to_string();
                 syn::parse_str::<syn::Type>(&name).ok()
             } else {
                 None
             }
         })
-        .unwrap_or_else(|| syn::parse_str::<syn::Type>("RangeRule").unwrap());
+        .unwrap_or_else(|| syn::parse_quote!(RangeRule));
     (pagination, filter, sort, range)
 }
 
+/// Function to parse the argument of an attribute, such as `#[grant(device_code)]`
+fn get_attribute_arg<T: syn::parse::Parse>(
+    ast: &syn::DeriveInput,
+    name: &str,
+) -> Option<syn::Result<T>> {
+    ast.attrs
+        .iter()
+        .find(|attr| attr.path().is_ident(name))
+        .map(|attr| attr.parse_args::<T>())
+}
+
+/// Function to check if the struct has a named field
+fn has_field(ast: &syn::DeriveInput, name: &str) -> bool {
+    match &ast.data {
+        syn::Data::Struct(data) => data
+            .fields
+            .iter()
+            .any(|field| field.ident.as_ref().is_some_and(|ident| ident == name)),
+        _ => false,
+    }
+}
+
+/// Function to select the secret auth method supported by the discovered provider,
+/// unless the `auth_method` attribute sets it
+fn secret_auth_method_impl(ast: &syn::DeriveInput) -> impl quote::ToTokens {
+    let explicit = ast
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("auth_method"));
+    (!explicit).then(|| quote! { .auth_method(provider.secret_auth_method()) })
+}
+
+/// Function to build the location of the API key
+/// - `api_key_header`, `api_key_query` or `api_key_cookie` attribute, with the name of the header, parameter or cookie
+/// - `X-API-Key` header by default
+fn api_key_location_impl(ast: &syn::DeriveInput) -> syn::Result<impl quote::ToTokens> {
+    let locations = [
+        ("api_key_header", quote! { ApiKeyLocation::Header }),
+        ("api_key_query", quote! { ApiKeyLocation::Query }),
+        ("api_key_cookie", quote! { ApiKeyLocation::Cookie }),
+    ];
+    let mut found = Vec::new();
+    for (attribute, variant) in locations {
+        if let Some(name) = get_attribute_arg::<syn::LitStr>(ast, attribute).transpose()? {
+            found.push(quote! { #variant(String::from(#name)) });
+        }
+    }
+    match found.len() {
+        0 => Ok(quote! { ApiKeyLocation::default() }),
+        1 => Ok(found.remove(0)),
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "Only one of api_key_header, api_key_query or api_key_cookie can be given !",
+        )),
+    }
+}
+
+/// Function to build the client authentication to the token endpoint, chained to the OAuth2Client
+/// - `client_secret` field, for confidential clients
+/// - `auth_method` attribute: client_secret_post, client_secret_basic, private_key_jwt, tls_client_auth or none
+/// - `private_key` and `key_id` fields, signed with the `signing_algorithm` attribute, RS256 (Default) or ES256
+/// - `tls_certificate` and `tls_key` fields, in PEM
+/// - `audience` and `resource` fields
+fn client_auth_impl(ast: &syn::DeriveInput) -> syn::Result<impl quote::ToTokens> {
+    let secret =
+        has_field(ast, "client_secret").then(|| quote! { .client_secret(&self.client_secret) });
+    let method = match get_attribute_arg::<Ident>(ast, "auth_method").transpose()? {
+        None => quote! {},
+        Some(method) => match method.to_string().as_str() {
+            "client_secret_post" => quote! { .auth_method(ClientAuthMethod::ClientSecretPost) },
+            "client_secret_basic" => quote! { .auth_method(ClientAuthMethod::ClientSecretBasic) },
+            "none" => quote! { .auth_method(ClientAuthMethod::None) },
+            "private_key_jwt" => {
+                let algorithm = get_attribute_arg::<Ident>(ast, "signing_algorithm")
+                    .transpose()?
+                    .unwrap_or_else(|| Ident::new("RS256", method.span()));
+                if algorithm != "RS256" && algorithm != "ES256" {
+                    return Err(syn::Error::new_spanned(
+                        algorithm,
+                        "signing_algorithm must be RS256 or ES256 !",
+                    ));
+                }
+                let key_id = has_field(ast, "key_id").then(|| quote! { .key_id(&self.key_id) });
+                quote! {
+                    .private_key_jwt(
+                        ClientAssertionKey::from_pem(SigningAlgorithm::#algorithm, &self.private_key)? #key_id
+                    )
+                }
+            }
+            "tls_client_auth" => quote! { .tls_client_auth(&self.tls_certificate, &self.tls_key)? },
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    method,
+                    "auth_method must be client_secret_post, client_secret_basic, private_key_jwt, tls_client_auth or none !",
+                ))
+            }
+        },
+    };
+    let audience = has_field(ast, "audience").then(|| quote! { .audience(&self.audience) });
+    let resource = has_field(ast, "resource").then(|| quote! { .resource(&self.resource) });
+    Ok(quote! { #secret #method #audience #resource })
+}
+
 /// Only impl the Authorization trait for the struct, with the default implementation.
 fn impl_authorization_derive(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
@@ -148,62 +340,12 @@ fn impl_authorization_derive(ast: &syn::DeriveInput) -> TokenStream {
 }
 
 /// Impl the Authorization trait for the struct, with the OAuth2 implementation.\
+/// The token is requested with the grant of the `grant` attribute, and renewed by the TokenSource of the API.\
 /// The trait accept the pagination, filter, sort and range types as attributes. (Optionals)\
 /// We use the AST to find the attributes (pagination, filter, sort and range) and parse them to the correct type.\
 /// If the attribute is not found, we use the default type.
 fn impl_oauth2_derive(ast: &syn::DeriveInput) -> TokenStream {
-    let name = &ast.ident;
-    let (pagination, filter, sort, range) = get_attribute_types(ast);
-    let token_struct_name = syn::Ident::new(&format!("{name}TokenOAuth2"), name.span());
-    let gen = quote! {
-        #[derive(Deserialize)]
-        struct #token_struct_name {
-            access_token: String,
-        }
-        impl Authorization<#pagination, #filter, #sort, #range> for #name {
-            async fn connect(&self, url: &str) -> Result<Api<#pagination, #filter, #sort, #range>> {
-                let connector = ApiBuilder::new(url);
-                let client = Client::new();
-
-                let scopes = self
-                    .scopes
-                    .iter()
-                    .fold(String::new(), |acc, scope| format!("{acc} {scope}"));
-                let mut params = HashMap::new();
-                params.insert("grant_type", "client_credentials");
-                params.insert("client_id", &self.client_id);
-                params.insert("client_secret", &self.client_secret);
-                params.insert("scope", &scopes);
-                match client
-                    .post(&self.auth_endpoint)
-                    .header("Content-Type", "application/x-www-form-urlencoded")
-                    .form(&params)
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        match response.status() {
-                            StatusCode::OK
-                            | StatusCode::CREATED
-                            | StatusCode::ACCEPTED
-                            | StatusCode::NO_CONTENT => {}
-                            status => return Err(status.into()),
-                        }
-                        match response.text().await {
-                            Ok(response_text) => {
-                                let token: #token_struct_name =
-                                    serde_json::from_str(&response_text).unwrap();
-                                Ok(connector.oauth2(token.access_token).build())
-                            }
-                            Err(e) => Err(ApiError::ResponseToText(e)),
-                        }
-                    }
-                    Err(e) => Err(ApiError::ReqwestExecute(e)),
-                }
-            }
-        }
-    };
-    gen.into()
+    oauth2_authorization_impl(ast, false)
 }
 
 /// Impl the Authorization trait for the struct, with the Basic implementation.\
@@ -254,13 +396,16 @@ fn impl_bearer_derive(ast: &syn::DeriveInput) -> TokenStream {
 fn impl_apikey_derive(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
     let (pagination, filter, sort, range) = get_attribute_types(ast);
+    let location = match api_key_location_impl(ast) {
+        Ok(location) => location,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let gen = quote! {
         impl Authorization<#pagination, #filter, #sort, #range> for #name {
             async fn connect(&self, url: &str) -> Result<Api<#pagination, #filter, #sort, #range>> {
                 let connector = ApiBuilder::new(url);
-                let client = Client::new();
 
-                Ok(connector.apikey(&self.key).build())
+                Ok(connector.api_key_in(&self.key, #location).build())
             }
         }
     };
@@ -268,58 +413,85 @@ fn impl_apikey_derive(ast: &syn::DeriveInput) -> TokenStream {
 }
 
 /// Impl the Authorization trait for the struct, with the OIDC implementation.\
+/// The token is requested with the grant of the `grant` attribute, and renewed by the TokenSource of the API.\
 /// The trait accept the pagination, filter, sort and range types as attributes. (Optionals)\
 /// We use the AST to find the attributes (pagination, filter, sort and range) and parse them to the correct type.\
 /// If the attribute is not found, we use the default type.
 fn impl_oidc_derive(ast: &syn::DeriveInput) -> TokenStream {
+    oauth2_authorization_impl(ast, has_field(ast, "issuer"))
+}
+
+/// Impl the Authorization trait for the struct, with a token source getting its token from the grant attribute.\
+/// With discovery, the endpoints are read from the discovery document of the `issuer` field.
+fn oauth2_authorization_impl(ast: &syn::DeriveInput, discovery: bool) -> TokenStream {
     let name = &ast.ident;
     let (pagination, filter, sort, range) = get_attribute_types(ast);
-    let token_struct_name = syn::Ident::new(&format!("{name}TokenOIDC"), name.span());
-    let gen = quote! {
-        #[derive(Deserialize)]
-        struct #token_struct_name {
-            access_token: String,
+    let grant = match get_attribute_arg::<Ident>(ast, "grant") {
+        Some(Ok(grant)) => grant.to_string(),
+        Some(Err(e)) => return e.to_compile_error().into(),
+        None => String::from("client_credentials"),
+    };
+    let on_user_code = match get_attribute_arg::<syn::Path>(ast, "on_user_code") {
+        Some(Ok(function)) => function,
+        Some(Err(e)) => return e.to_compile_error().into(),
+        None => syn::parse_quote!(DeviceAuthorization::log),
+    };
+    let client_auth = match client_auth_impl(ast) {
+        Ok(client_auth) => client_auth,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let secret_method = secret_auth_method_impl(ast);
+    let (client, discover) = match discovery {
+        true => (
+            quote! { let client = OAuth2Client::new(&self.issuer, &self.client_id) },
+            quote! {
+                let provider = ProviderMetadata::discover(client.http(), &self.issuer).await?;
+                let client = provider.configure(client)? #secret_method;
+            },
+        ),
+        false => (
+            quote! { let client = OAuth2Client::new(&self.auth_endpoint, &self.client_id) },
+            quote! {},
+        ),
+    };
+    let connect = match (grant.as_str(), discovery) {
+        ("client_credentials", _) => {
+            let check =
+                discovery.then(|| quote! { provider.require_grant("client_credentials")?; });
+            let authorization = match discovery {
+                true => quote! { oidc },
+                false => quote! { oauth2 },
+            };
+            quote! {
+                #check
+                let source = TokenSource::new(client, Grant::ClientCredentials);
+                let token = source.token().await?;
+                Ok(ApiBuilder::new(url).#authorization(token).token_source(source).build())
+            }
+        }
+        ("device_code", true) => quote! {
+            provider.device_code_flow(client)?.connect(url, #on_user_code).await
+        },
+        ("device_code", false) => quote! {
+            DeviceCodeFlow::new(client, &self.device_endpoint)
+                .connect(url, #on_user_code)
+                .await
+        },
+        _ => {
+            return quote! {
+                compile_error!("grant must be client_credentials or device_code !");
+            }
+            .into()
         }
+    };
+    let gen = quote! {
         impl Authorization<#pagination, #filter, #sort, #range> for #name {
             async fn connect(&self, url: &str) -> Result<Api<#pagination, #filter, #sort, #range>> {
-                let connector = ApiBuilder::new(url);
-                let client = Client::new();
-
-                let scopes = self
-                    .scopes
-                    .iter()
-                    .fold(String::new(), |acc, scope| format!("{acc} {scope}"));
-                let mut params = HashMap::new();
-                params.insert("grant_type", "client_credentials");
-                params.insert("client_id", &self.client_id);
-                params.insert("client_secret", &self.client_secret);
-                params.insert("scope", &scopes);
-                match client
-                    .post(&self.auth_endpoint)
-                    .header("Content-Type", "application/x-www-form-urlencoded")
-                    .form(&params)
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        match response.status() {
-                            StatusCode::OK
-                            | StatusCode::CREATED
-                            | StatusCode::ACCEPTED
-                            | StatusCode::NO_CONTENT => {}
-                            status => return Err(status.into()),
-                        }
-                        match response.text().await {
-                            Ok(response_text) => {
-                                let token: #token_struct_name =
-                                    serde_json::from_str(&response_text).unwrap();
-                                Ok(connector.oidc(token.access_token).build())
-                            }
-                            Err(e) => Err(ApiError::ResponseToText(e)),
-                        }
-                    }
-                    Err(e) => Err(ApiError::ReqwestExecute(e)),
-                }
+                #client
+                    #client_auth
+                    .scopes(&self.scopes);
+                #discover
+                #connect
             }
         }
     };
@@ -338,7 +510,7 @@ fn impl_keycloak_derive(ast: &syn::DeriveInput) -> TokenStream {
                 ..
             } = attr
             {
-                let name = token.clone().into_iter().next().unwrap().to_string();
+                let name = token.clone().into_iter().next()?.to_string();
                 syn::parse_str::<Variant>(&name).ok()
             } else {
                 None
@@ -352,93 +524,138 @@ fn impl_keycloak_derive(ast: &syn::DeriveInput) -> TokenStream {
         }
         .into();
     };
-    let name = &ast.ident;
-    let (pagination, filter, sort, range) = get_attribute_types(ast);
     let auth_variant = auth_type.ident;
     match auth_variant.to_string().as_str() {
-        "None" | "Basic" | "Bearer" | "ApiKey" | "OAuth2" => keycloak_authorization_impl(
-            auth_variant.to_string(),
-            pagination,
-            filter,
-            sort,
-            range,
-            name,
-        ),
+        "None" | "Basic" | "Bearer" | "ApiKey" | "OAuth2" | "OIDC" => {
+            keycloak_authorization_impl(ast, auth_variant)
+        }
         _ => quote! {
             compile_error!(
-                "AuthorizationType must be None, Basic, Bearer, ApiKey or OAuth2 !"
+                "AuthorizationType must be None, Basic, Bearer, ApiKey, OAuth2 or OIDC !"
             );
         }
         .into(),
     }
 }
 
-/// Impl the Authorization trait for the struct, with the Keycloak implementation.
-fn keycloak_authorization_impl(
-    auth_type: String,
-    pagination: Type,
-    filter: Type,
-    sort: Type,
-    range: Type,
-    name: &Ident,
-) -> TokenStream {
-    let token_struct_name = syn::Ident::new(&format!("{name}TokenKeycloak"), name.span());
-    let gen = quote! {
-        #[derive(Deserialize)]
-        struct #token_struct_name {
-            access_token: String,
+/// Impl the Authorization trait for the struct, with the Keycloak implementation.\
+/// The token endpoint is discovered from the issuer of the realm, `{auth_endpoint}realms/{realm}`,
+/// or is `{auth_endpoint}realms/{realm}/protocol/openid-connect/token` when the discovery fails.\
+/// The grant is selected with the `grant` attribute, and `#[offline_access]` requests an offline token.\
+/// Without a `client_secret` field or an `auth_method` attribute, the client is public and only sends its identifier.\
+/// The secret is sent with HTTP Basic authentication, unless the `auth_method` attribute is given.\
+/// Without authorization, no token is requested.\
+/// With the Bearer, OAuth2 and OIDC authorization types, the token is renewed by the TokenSource of the API.
+fn keycloak_authorization_impl(ast: &syn::DeriveInput, auth_type: Ident) -> TokenStream {
+    let name = &ast.ident;
+    let (pagination, filter, sort, range) = get_attribute_types(ast);
+    let grant = match get_attribute_arg::<Ident>(ast, "grant") {
+        Some(Ok(grant)) => grant.to_string(),
+        Some(Err(e)) => return e.to_compile_error().into(),
+        None => String::from("password"),
+    };
+    let (grant_type, grant) = match grant.as_str() {
+        "password" => (
+            quote! { "password" },
+            quote! {
+                Grant::Password {
+                    username: self.user_login.clone(),
+                    password: self.user_pass.clone(),
+                }
+            },
+        ),
+        "client_credentials" => (
+            quote! { "client_credentials" },
+            quote! { Grant::ClientCredentials },
+        ),
+        "refresh_token" => (
+            quote! { "refresh_token" },
+            quote! { Grant::RefreshToken(self.refresh_token.clone()) },
+        ),
+        "token_exchange" => {
+            let options = ["audience", "requested_subject", "subject_issuer"]
+                .into_iter()
+                .filter(|option| has_field(ast, option))
+                .map(|option| {
+                    let option = Ident::new(option, name.span());
+                    quote! { .#option(&self.#option) }
+                });
+            (
+                quote! { "urn:ietf:params:oauth:grant-type:token-exchange" },
+                quote! { Grant::TokenExchange(TokenExchange::new(&self.subject_token) #(#options)*) },
+            )
         }
-        impl Authorization<#pagination, #filter, #sort, #range> for #name {
-            async fn connect(&self, url: &str) -> Result<Api<#pagination, #filter, #sort, #range>> {
-                let connector = ApiBuilder::new(url);
-                let client = Client::new();
-
-                let auth_header = format!(
-                    "Basic {}",
-                    general_purpose::STANDARD_NO_PAD.encode(format!("{}:{}", &self.client_id, &self.client_secret))
+        _ => {
+            return quote! {
+                compile_error!(
+                    "grant must be password, client_credentials, refresh_token or token_exchange !"
                 );
-                let mut params = HashMap::new();
-                params.insert("grant_type", "password");
-                params.insert("username", &self.user_login);
-                params.insert("password", &self.user_pass);
-                match client
-                    .post(format!(
-                        "{}realms/{}/protocol/openid-connect/token",
-                        self.auth_endpoint, self.realm
-                    ))
-                    .header("Content-Type", "application/x-www-form-urlencoded")
-                    .header("Authorization", auth_header)
-                    .form(&params)
-                    .send()
-                    .await
-                {
-                    Ok(response) => {
-                        log::info!("{:?}", response);
-                        match response.status() {
-                            StatusCode::OK
-                            | StatusCode::CREATED
-                            | StatusCode::ACCEPTED
-                            | StatusCode::NO_CONTENT => {}
-                            status => return Err(status.into()),
-                        }
-                        match response.text().await {
-                            Ok(response_text) => {
-                                let token: #token_struct_name =
-                                    serde_json::from_str(&response_text).unwrap();
-                                Ok(connector.keycloak(match #auth_type {
-                                    "None" => AuthorizationType::None,
-                                    "Basic" => AuthorizationType::Basic(token.access_token),
-                                    "Bearer" => AuthorizationType::Bearer(token.access_token),
-                                    "ApiKey" => AuthorizationType::ApiKey(token.access_token),
-                                    "OAuth2" => AuthorizationType::OAuth2(token.access_token),
-                                    _ => return Err(ApiError::AuthorizationType),
-                                }).build())
-                            }
-                            Err(e) => Err(ApiError::ResponseToText(e)),
-                        }
+            }
+            .into()
+        }
+    };
+    let client_auth = match client_auth_impl(ast) {
+        Ok(client_auth) => client_auth,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // The secret is sent with HTTP Basic authentication, whatever the provider discovers
+    let basic = (!ast
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("auth_method")))
+    .then(|| quote! { .auth_method(ClientAuthMethod::ClientSecretBasic) });
+    let mut scopes = match has_field(ast, "scopes") {
+        true => quote! { let mut scopes = self.scopes.clone(); },
+        false => quote! { let mut scopes: Vec<String> = Vec::new(); },
+    };
+    if ast
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("offline_access"))
+    {
+        scopes.extend(quote! { scopes.push(String::from("offline_access")); });
+    }
+    let authorization = match auth_type.to_string().as_str() {
+        "None" => quote! { AuthorizationType::None },
+        "ApiKey" => match api_key_location_impl(ast) {
+            Ok(location) => quote! { AuthorizationType::ApiKey(source.token().await?, #location) },
+            Err(e) => return e.to_compile_error().into(),
+        },
+        _ => quote! { AuthorizationType::#auth_type(source.token().await?) },
+    };
+    let connector = match auth_type.to_string().as_str() {
+        "Bearer" | "OAuth2" | "OIDC" => quote! { connector.token_source(source) },
+        _ => quote! { connector },
+    };
+    // Without authorization, neither the provider nor the token endpoint is requested
+    let (discover, source) = match auth_type.to_string().as_str() {
+        "None" => (quote! {}, quote! { _source }),
+        _ => (
+            quote! {
+                let client = match ProviderMetadata::try_discover(client.http(), &issuer).await {
+                    Some(provider) => {
+                        provider.require_grant(#grant_type)?;
+                        provider.configure(client)?
                     }
-                    Err(e) => Err(ApiError::ReqwestExecute(e)),
-                }
+                    None => client,
+                };
+            },
+            quote! { source },
+        ),
+    };
+    let gen = quote! {
+        impl Authorization<#pagination, #filter, #sort, #range> for #name {
+            async fn connect(&self, url: &str) -> Result<Api<#pagination, #filter, #sort, #range>> {
+                let issuer = format!("{}realms/{}", self.auth_endpoint, self.realm);
+                #scopes
+                let client = OAuth2Client::new(
+                    format!("{issuer}/protocol/openid-connect/token"),
+                    &self.client_id,
+                ) #client_auth #basic .scopes(scopes);
+                #discover
+                let #source = TokenSource::new(client, #grant);
+                let connector = ApiBuilder::new(url).keycloak(#authorization);
+                Ok(#connector.build())
             }
         }
     };
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, time::Duration};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    connector::Api,
+    connector_builder::ApiBuilder,
+    error::{ApiError, Result},
+    filter::Filter,
+    oauth2::{Grant, OAuth2Client, TokenSource},
+    pagination::Pagination,
+    query::Query,
+    range::Range,
+    sort::Sort,
+};
+
+/// Maximum size of the redirect request read by the loopback listener
+const REDIRECT_REQUEST_LIMIT: usize = 16 * 1024;
+
+/// Time given to a connection of the loopback listener to send its request, as browsers open idle connections
+const REDIRECT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+const REDIRECT_RESPONSE: &str =
+    "<html><body>Authorization complete, you can close this window.</body></html>";
+
+/// Proof Key for Code Exchange (RFC 7636)
+///
+/// # Attributes
+/// * verifier - Random secret sent with the code to the token endpoint
+/// * challenge - SHA-256 of the verifier, sent with the authorization request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    /// Generate a random verifier of 43 characters and its S256 challenge
+    pub fn new() -> Self {
+        Self::from_verifier(URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>()))
+    }
+
+    pub fn from_verifier(verifier: impl ToString) -> Self {
+        let verifier = verifier.to_string();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        Self {
+            verifier,
+            challenge,
+        }
+    }
+}
+
+impl Default for Pkce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// OAuth2 authorization code flow with PKCE, redirecting the user to a loopback listener (RFC 8252)
+///
+/// The user opens the authorization URL in a browser, and the authorization server redirects them
+/// to a temporary HTTP listener on localhost, which captures the code.\
+/// The code is then exchanged for a token at the token endpoint.
+///
+/// # Attributes
+/// * client - Client of the token endpoint, with the scopes to request
+/// * authorization_endpoint - URL of the authorization endpoint
+/// * redirect_port - Port of the loopback listener (Default = 0, any free port)
+/// * redirect_path - Path of the redirect URI (Default = `/callback`)
+/// * timeout - Maximum time to wait for the redirect (Default = 5 minutes)
+/// * params - Additional parameters of the authorization request
+///
+/// # Example
+/// ```rust,ignore
+/// let client = OAuth2Client::new("https://auth.example.com/token", "cli").scopes(["read"]);
+/// let api: Api = AuthorizationCodeFlow::new(client, "https://auth.example.com/authorize")
+///     .connect("https://api.example.com", |url| println!("Open {url} in your browser"))
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuthorizationCodeFlow {
+    pub(crate) client: OAuth2Client,
+    pub(crate) authorization_endpoint: String,
+    pub(crate) redirect_port: u16,
+    pub(crate) redirect_path: String,
+    pub(crate) timeout: Duration,
+    pub(crate) params: Vec<(String, String)>,
+}
+
+impl AuthorizationCodeFlow {
+    pub fn new(client: OAuth2Client, authorization_endpoint: impl ToString) -> Self {
+        Self {
+            client,
+            authorization_endpoint: authorization_endpoint.to_string(),
+            redirect_port: 0,
+            redirect_path: String::from("/callback"),
+            timeout: Duration::from_secs(300),
+            params: Vec::new(),
+        }
+    }
+
+    /// Set the port of the loopback listener, for servers requiring an exact redirect URI
+    pub fn redirect_port(mut self, port: u16) -> Self {
+        self.redirect_port = port;
+        self
+    }
+
+    pub fn redirect_path(mut self, path: impl ToString) -> Self {
+        self.redirect_path = path.to_string();
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Add a parameter to the authorization request, such as `prompt` or `login_hint`
+    pub fn param(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.params.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Build the URL of the authorization request
+    pub fn authorize_url(&self, redirect_uri: &str, state: &str, pkce: &Pkce) -> Result<Url> {
+        let mut url = Url::parse(&self.authorization_endpoint)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &self.client.client_id)
+                .append_pair("redirect_uri", redirect_uri)
+                .append_pair("state", state)
+                .append_pair("code_challenge", &pkce.challenge)
+                .append_pair("code_challenge_method", "S256");
+            if !self.client.scopes.is_empty() {
+                query.append_pair("scope", &self.client.scopes.join(" "));
+            }
+            for (key, value) in self.client.audience_params() {
+                query.append_pair(key, &value);
+            }
+            for (key, value) in &self.params {
+                query.append_pair(key, value);
+            }
+        }
+        Ok(url)
+    }
+
+    /// Run the flow and return the token source of the user
+    ///
+    /// `open` is called with the authorization URL, to open it in a browser or show it to the user
+    pub async fn authorize(&self, open: impl FnOnce(&Url)) -> Result<TokenSource> {
+        let listener = TcpListener::bind(("127.0.0.1", self.redirect_port))
+            .await
+            .map_err(|e| ApiError::OAuth2Flow(format!("Redirect listener: {e}")))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| ApiError::OAuth2Flow(format!("Redirect listener: {e}")))?
+            .port();
+        let redirect_uri = format!("http://127.0.0.1:{port}{}", self.redirect_path);
+        let state = URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>());
+        let pkce = Pkce::new();
+
+        open(&self.authorize_url(&redirect_uri, &state, &pkce)?);
+        let params = tokio::time::timeout(self.timeout, self.wait_for_redirect(&listener))
+            .await
+            .map_err(|_| {
+                ApiError::OAuth2Flow(String::from("Timed out waiting for the redirect"))
+            })??;
+
+        if params.get("state") != Some(&state) {
+            return Err(ApiError::OAuth2Flow(String::from(
+                "State of the redirect does not match",
+            )));
+        }
+        if let Some(error) = params.get("error") {
+            let description = params.get("error_description").map_or("", String::as_str);
+            return Err(ApiError::OAuth2Flow(format!(
+                "Authorization denied: {error} {description}"
+            )));
+        }
+        let code = params
+            .get("code")
+            .ok_or_else(|| ApiError::OAuth2Flow(String::from("Redirect without a code")))?;
+        let grant = Grant::AuthorizationCode {
+            code: code.clone(),
+            redirect_uri,
+            code_verifier: Some(pkce.verifier),
+        };
+        let response = self.client.request_token(&grant).await?;
+        Ok(TokenSource::with_token(
+            self.client.clone(),
+            grant,
+            response,
+        ))
+    }
+
+    /// Run the flow and build an API using the token of the user
+    pub async fn connect<P: Pagination, F: Filter, S: Sort, R: Range>(
+        &self,
+        url: impl ToString,
+        open: impl FnOnce(&Url),
+    ) -> Result<Api<P, F, S, R>>
+    where
+        Query: for<'a> From<&'a F> + for<'a> From<&'a S> + for<'a> From<&'a R>,
+    {
+        let source = self.authorize(open).await?;
+        let token = source.token().await?;
+        Ok(ApiBuilder::new(url)
+            .oauth2(token)
+            .token_source(source)
+            .build())
+    }
+
+    /// Accept connections until the redirect URI is requested, and return its query parameters
+    ///
+    /// Idle connections and malformed requests are skipped
+    async fn wait_for_redirect(&self, listener: &TcpListener) -> Result<HashMap<String, String>> {
+        loop {
+            let (mut stream, _) = listener
+                .accept()
+                .await
+                .map_err(|e| ApiError::OAuth2Flow(format!("Redirect listener: {e}")))?;
+            let Ok(Some(target)) =
+                tokio::time::timeout(REDIRECT_READ_TIMEOUT, read_request_target(&mut stream)).await
+            else {
+                continue;
+            };
+            let Ok(url) = Url::parse("http://127.0.0.1").and_then(|base| base.join(&target)) else {
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+                continue;
+            };
+            if url.path() != self.redirect_path {
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+                continue;
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{REDIRECT_RESPONSE}",
+                REDIRECT_RESPONSE.len()
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            return Ok(url.query_pairs().into_owned().collect());
+        }
+    }
+}
+
+/// Read the head of an HTTP request, and return the target of its request line
+async fn read_request_target(stream: &mut TcpStream) -> Option<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < REDIRECT_REQUEST_LIMIT {
+        let read = stream.read(&mut buffer).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next()?.split_whitespace();
+    match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => Some(target.to_string()),
+        _ => None,
+    }
+}
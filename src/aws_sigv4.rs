@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::{AUTHORIZATION, HeaderValue};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{ApiError, Result},
+    request_signer::RequestSigner,
+};
+
+/// Payload hash sent when the body is not signed
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Headers never signed, because proxies and the HTTP client may change them
+const UNSIGNED_HEADERS: [&str; 4] = ["authorization", "user-agent", "expect", "x-amzn-trace-id"];
+
+/// Credentials of an AWS identity
+///
+/// # Attributes
+/// * access_key_id - Identifier of the access key
+/// * secret_access_key - Secret of the access key
+/// * session_token - Token of temporary credentials, sent as `x-amz-security-token`
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub(crate) access_key_id: String,
+    pub(crate) secret_access_key: String,
+    pub(crate) session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    pub fn new(access_key_id: impl ToString, secret_access_key: impl ToString) -> Self {
+        Self {
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            session_token: None,
+        }
+    }
+
+    pub fn session_token(mut self, token: impl ToString) -> Self {
+        self.session_token = Some(token.to_string());
+        self
+    }
+}
+
+/// The secret and the session token are never printed
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// AWS Signature Version 4 signer
+///
+/// The signer runs on the final request of every attempt, once the pagination, filter, sort and range
+/// parameters are in its URL.\
+/// It signs the method, the path, the query, the headers of the request and the hash of its body,
+/// and sets the `x-amz-date` and `Authorization` headers.
+///
+/// # Attributes
+/// * credentials - Credentials signing the requests
+/// * region - Region of the service, such as `eu-west-1`
+/// * service - Signing name of the service, such as `s3` or `execute-api`
+/// * unsigned_payload - Send `UNSIGNED-PAYLOAD` instead of the hash of the body (Default = false)
+///
+/// # Example
+/// ```rust,ignore
+/// let signer = AwsSigV4::new(AwsCredentials::new("AKID", "secret"), "eu-west-1", "execute-api");
+/// let api: Api = ApiBuilder::new("https://abc123.execute-api.eu-west-1.amazonaws.com/prod")
+///     .aws_sigv4(signer)
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct AwsSigV4 {
+    pub(crate) credentials: AwsCredentials,
+    pub(crate) region: String,
+    pub(crate) service: String,
+    pub(crate) unsigned_payload: bool,
+}
+
+impl AwsSigV4 {
+    pub fn new(credentials: AwsCredentials, region: impl ToString, service: impl ToString) -> Self {
+        Self {
+            credentials,
+            region: region.to_string(),
+            service: service.to_string(),
+            unsigned_payload: false,
+        }
+    }
+
+    /// Do not hash the body, for large uploads to S3
+    pub fn unsigned_payload(mut self, unsigned: bool) -> Self {
+        self.unsigned_payload = unsigned;
+        self
+    }
+
+    /// Sign the request at the given time
+    ///
+    /// Returns `ApiError::RequestSigning` if the body is a stream and the payload is signed
+    pub fn sign_at(&self, request: &mut reqwest::Request, time: DateTime<Utc>) -> Result<()> {
+        let amz_date = time.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = time.format("%Y%m%d").to_string();
+        let payload_hash = self.payload_hash(request)?;
+        let headers = request.headers_mut();
+        headers.remove(AUTHORIZATION);
+        headers.insert("x-amz-date", HeaderValue::from_str(&amz_date)?);
+        if let Some(token) = &self.credentials.session_token {
+            headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+        }
+        // S3 requires the hash of the payload in a header
+        if self.service == "s3" || self.unsigned_payload {
+            headers.insert(
+                "x-amz-content-sha256",
+                HeaderValue::from_str(&payload_hash)?,
+            );
+        }
+
+        let (signed_headers, canonical_headers) = Self::canonical_headers(request);
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method(),
+            self.canonical_uri(request.url()),
+            Self::canonical_query(request.url()),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{date}/{}/{}/aws4_request", self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut signing_key = hmac_sha256(
+            format!("AWS4{}", self.credentials.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        )?;
+        for part in [self.region.as_str(), self.service.as_str(), "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes())?;
+        }
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes())?);
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.credentials.access_key_id
+        );
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+        Ok(())
+    }
+
+    fn payload_hash(&self, request: &reqwest::Request) -> Result<String> {
+        if self.unsigned_payload {
+            return Ok(String::from(UNSIGNED_PAYLOAD));
+        }
+        match request.body().map(|body| body.as_bytes()) {
+            None => Ok(hex(&Sha256::digest(b""))),
+            Some(Some(bytes)) => Ok(hex(&Sha256::digest(bytes))),
+            Some(None) => Err(ApiError::RequestSigning(String::from(
+                "A streamed body can only be sent with an unsigned payload",
+            ))),
+        }
+    }
+
+    /// Path of the URL, encoded once more for every service but S3
+    fn canonical_uri(&self, url: &url::Url) -> String {
+        match self.service.as_str() {
+            "s3" => url.path().to_string(),
+            _ => uri_encode(url.path(), false),
+        }
+    }
+
+    /// Query parameters encoded and sorted by name, then by value
+    fn canonical_query(url: &url::Url) -> String {
+        let mut params: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| (uri_encode(&name, true), uri_encode(&value, true)))
+            .collect();
+        params.sort();
+        params
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Signed header names, and the canonical headers, with the host of the URL
+    fn canonical_headers(request: &reqwest::Request) -> (String, String) {
+        let mut headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, value) in request.headers() {
+            if UNSIGNED_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            let value = String::from_utf8_lossy(value.as_bytes());
+            headers
+                .entry(name.to_string())
+                .or_default()
+                .push(value.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        let url = request.url();
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        headers.entry(String::from("host")).or_insert(vec![host]);
+        let signed = headers.keys().cloned().collect::<Vec<_>>().join(";");
+        let canonical = headers
+            .iter()
+            .map(|(name, values)| format!("{name}:{}\n", values.join(",")))
+            .collect();
+        (signed, canonical)
+    }
+}
+
+impl RequestSigner for AwsSigV4 {
+    /// Sign the request at the current time
+    fn sign(&self, request: &mut reqwest::Request) -> Result<()> {
+        self.sign_at(request, Utc::now())
+    }
+}
+
+/// Percent encode everything but the unreserved characters, and `/` unless `encode_slash`
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            b'/' if !encode_slash => String::from("/"),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).map_err(|e| ApiError::RequestSigning(e.to_string()))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::error::{ApiError, Result};
+
+/// State of the circuit of a host
+///
+/// # Variants
+/// * Closed - Requests are sent, and their outcome is recorded
+/// * Open - Requests fail fast with `ApiError::CircuitOpen` until the cool-down is over
+/// * HalfOpen - A few probe requests are sent to check if the host recovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// Callback called with the host, the previous state and the new state of a circuit
+pub type StateChangeCallback = Arc<dyn Fn(&str, CircuitState, CircuitState) + Send + Sync>;
+
+/// Circuit breaker of an API, with one circuit per host
+///
+/// A circuit opens when the failure rate of its last `window` requests reaches `failure_rate`,
+/// once at least `minimum_requests` were recorded.\
+/// After `cool_down`, the circuit becomes half-open and lets `half_open_requests` probes through:
+/// the circuit closes if they all succeed, and opens again on the first failure.\
+/// Transport errors and 5xx responses are failures, any other response is a success.
+///
+/// # Attributes
+/// * failure_rate - Rate of failures opening the circuit, above 0 and up to 1
+/// * minimum_requests - Number of requests recorded before the failure rate is checked
+/// * window - Number of last requests used to compute the failure rate
+/// * cool_down - Time the circuit stays open before probing the host
+/// * half_open_requests - Number of successful probes needed to close the circuit
+/// * on_state_change - Callback called on every state change
+///
+/// # Example
+/// ```rust,ignore
+/// let breaker = CircuitBreaker::new(0.5, Duration::from_secs(30))
+///     .minimum_requests(20)
+///     .on_state_change(|host, from, to| log::warn!("{host}: {from} -> {to}"));
+/// let api = ApiBuilder::new("https://api.example.com")
+///     .circuit_breaker(breaker)
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    pub(crate) failure_rate: f64,
+    pub(crate) minimum_requests: usize,
+    pub(crate) window: usize,
+    pub(crate) cool_down: Duration,
+    pub(crate) half_open_requests: u32,
+    pub(crate) on_state_change: Option<StateChangeCallback>,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+
+impl Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("failure_rate", &self.failure_rate)
+            .field("minimum_requests", &self.minimum_requests)
+            .field("window", &self.window)
+            .field("cool_down", &self.cool_down)
+            .field("half_open_requests", &self.half_open_requests)
+            .field("circuits", &self.circuits)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Circuit of one host
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    outcomes: VecDeque<bool>,
+    opened_at: Instant,
+    probes: u32,
+    successful_probes: u32,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            outcomes: VecDeque::new(),
+            opened_at: Instant::now(),
+            probes: 0,
+            successful_probes: 0,
+        }
+    }
+
+    fn transition(&mut self, state: CircuitState) -> (CircuitState, CircuitState) {
+        let previous = self.state;
+        self.state = state;
+        self.outcomes.clear();
+        self.probes = 0;
+        self.successful_probes = 0;
+        if state != CircuitState::Closed {
+            self.opened_at = Instant::now();
+        }
+        (previous, state)
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker opening at `failure_rate` failures for `cool_down`
+    ///
+    /// A `failure_rate` of 0 or less is raised to `f64::EPSILON`, the circuit opens on the first failure
+    ///
+    /// # Default
+    /// * minimum_requests - 10
+    /// * window - 20
+    /// * half_open_requests - 1
+    /// * on_state_change - None
+    pub fn new(failure_rate: f64, cool_down: Duration) -> Self {
+        Self {
+            failure_rate: failure_rate.clamp(f64::EPSILON, 1.),
+            minimum_requests: 10,
+            window: 20,
+            cool_down,
+            half_open_requests: 1,
+            on_state_change: None,
+            circuits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn minimum_requests(mut self, minimum: usize) -> Self {
+        self.minimum_requests = minimum.max(1);
+        self
+    }
+
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    pub fn half_open_requests(mut self, requests: u32) -> Self {
+        self.half_open_requests = requests.max(1);
+        self
+    }
+
+    /// Set the callback called with the host, the previous state and the new state
+    /// every time a circuit changes state
+    pub fn on_state_change(
+        mut self,
+        callback: impl Fn(&str, CircuitState, CircuitState) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_state_change = Some(Arc::new(callback));
+        self
+    }
+
+    /// Current state of the circuit of a host
+    ///
+    /// An open circuit is reported half-open once its cool-down is over
+    pub fn state(&self, host: &str) -> CircuitState {
+        match self.lock().get(host) {
+            Some(circuit)
+                if circuit.state == CircuitState::Open
+                    && circuit.opened_at.elapsed() >= self.cool_down =>
+            {
+                CircuitState::HalfOpen
+            }
+            Some(circuit) => circuit.state,
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// Check if a request can be sent to the host
+    ///
+    /// Returns `ApiError::CircuitOpen` if the circuit is open,
+    /// or if every probe of the half-open circuit is already in flight
+    pub(crate) fn try_acquire(&self, host: &str) -> Result<()> {
+        let mut change = None;
+        let result = {
+            let mut circuits = self.lock();
+            let circuit = circuits
+                .entry(host.to_string())
+                .or_insert_with(Circuit::new);
+            if circuit.state == CircuitState::Open && circuit.opened_at.elapsed() >= self.cool_down
+            {
+                change = Some(circuit.transition(CircuitState::HalfOpen));
+            } else if circuit.state == CircuitState::HalfOpen
+                && circuit.opened_at.elapsed() >= self.cool_down
+            {
+                // The probes never reported their outcome, such as a cancelled request
+                circuit.probes = circuit.successful_probes;
+                circuit.opened_at = Instant::now();
+            }
+            match circuit.state {
+                CircuitState::Closed => Ok(()),
+                CircuitState::HalfOpen if circuit.probes < self.half_open_requests => {
+                    circuit.probes += 1;
+                    Ok(())
+                }
+                _ => Err(ApiError::CircuitOpen(host.to_string())),
+            }
+        };
+        self.notify(host, change);
+        result
+    }
+
+    /// Record the outcome of a request sent to the host
+    pub(crate) fn record(&self, host: &str, success: bool) {
+        let change = {
+            let mut circuits = self.lock();
+            let circuit = circuits
+                .entry(host.to_string())
+                .or_insert_with(Circuit::new);
+            match circuit.state {
+                CircuitState::Closed => {
+                    circuit.outcomes.push_back(success);
+                    while circuit.outcomes.len() > self.window {
+                        circuit.outcomes.pop_front();
+                    }
+                    let failures = circuit.outcomes.iter().filter(|s| !**s).count();
+                    let total = circuit.outcomes.len();
+                    (total >= self.minimum_requests
+                        && failures as f64 / total as f64 >= self.failure_rate)
+                        .then(|| circuit.transition(CircuitState::Open))
+                }
+                CircuitState::HalfOpen if !success => Some(circuit.transition(CircuitState::Open)),
+                CircuitState::HalfOpen => {
+                    circuit.successful_probes += 1;
+                    (circuit.successful_probes >= self.half_open_requests)
+                        .then(|| circuit.transition(CircuitState::Closed))
+                }
+                CircuitState::Open => None,
+            }
+        };
+        self.notify(host, change);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Circuit>> {
+        self.circuits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Log the state change and call the callback, outside of the lock
+    fn notify(&self, host: &str, change: Option<(CircuitState, CircuitState)>) {
+        let Some((from, to)) = change else {
+            return;
+        };
+        log::info!("Circuit of {host} changed from {from} to {to}");
+        if let Some(callback) = &self.on_state_change {
+            callback(host, from, to);
+        }
+    }
+}
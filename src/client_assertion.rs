@@ -0,0 +1,118 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Serialize;
+
+use crate::error::{ApiError, Result};
+
+/// Type of the client assertion sent with the `private_key_jwt` auth method (RFC 7523 section 2.2)
+pub const JWT_BEARER_ASSERTION_TYPE: &str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Algorithm signing the client assertions
+///
+/// # Variants
+/// * RS256 - RSASSA-PKCS1-v1_5 with SHA-256, from an RSA key
+/// * ES256 - ECDSA with the P-256 curve and SHA-256, from an EC key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display, strum::EnumString)]
+pub enum SigningAlgorithm {
+    #[default]
+    RS256,
+    ES256,
+}
+
+/// Claims of a client assertion (RFC 7523 section 3)
+#[derive(Debug, Serialize)]
+struct ClientAssertionClaims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    aud: &'a str,
+    jti: String,
+    iat: u64,
+    exp: u64,
+}
+
+/// Private key signing the client assertions of the `private_key_jwt` auth method
+///
+/// A new assertion is signed for every call to the token endpoint,
+/// with the client as issuer and subject, and the token endpoint as audience.
+///
+/// # Attributes
+/// * algorithm - Algorithm of the signature
+/// * key - Private key, parsed from PEM
+/// * key_id - Identifier of the key in the JWKS of the client, sent as `kid`
+/// * lifetime - Time before the assertion expires (Default = 60s)
+///
+/// # Example
+/// ```rust,ignore
+/// let key = ClientAssertionKey::from_pem(SigningAlgorithm::ES256, include_bytes!("client.pem"))?
+///     .key_id("2024-01");
+/// let client = OAuth2Client::new("https://auth.example.com/token", "client_id").private_key_jwt(key);
+/// ```
+#[derive(Clone)]
+pub struct ClientAssertionKey {
+    pub(crate) algorithm: SigningAlgorithm,
+    pub(crate) key: EncodingKey,
+    pub(crate) key_id: Option<String>,
+    pub(crate) lifetime: Duration,
+}
+
+impl ClientAssertionKey {
+    /// Parse a PEM private key, PKCS#1 or PKCS#8 for RS256, SEC1 or PKCS#8 for ES256
+    pub fn from_pem(algorithm: SigningAlgorithm, pem: impl AsRef<[u8]>) -> Result<Self> {
+        let key = match algorithm {
+            SigningAlgorithm::RS256 => EncodingKey::from_rsa_pem(pem.as_ref()),
+            SigningAlgorithm::ES256 => EncodingKey::from_ec_pem(pem.as_ref()),
+        }
+        .map_err(ApiError::ClientAssertion)?;
+        Ok(Self {
+            algorithm,
+            key,
+            key_id: None,
+            lifetime: Duration::from_secs(60),
+        })
+    }
+
+    pub fn key_id(mut self, key_id: impl ToString) -> Self {
+        self.key_id = Some(key_id.to_string());
+        self
+    }
+
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Sign an assertion of the client for the audience
+    pub fn assertion(&self, client_id: &str, audience: &str) -> Result<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = ClientAssertionClaims {
+            iss: client_id,
+            sub: client_id,
+            aud: audience,
+            jti: uuid::Uuid::new_v4().to_string(),
+            iat: now,
+            exp: now + self.lifetime.as_secs(),
+        };
+        let mut header = Header::new(match self.algorithm {
+            SigningAlgorithm::RS256 => Algorithm::RS256,
+            SigningAlgorithm::ES256 => Algorithm::ES256,
+        });
+        header.kid = self.key_id.clone();
+        jsonwebtoken::encode(&header, &claims, &self.key).map_err(ApiError::ClientAssertion)
+    }
+}
+
+/// The key itself is never printed
+impl std::fmt::Debug for ClientAssertionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientAssertionKey")
+            .field("algorithm", &self.algorithm)
+            .field("key_id", &self.key_id)
+            .field("lifetime", &self.lifetime)
+            .finish_non_exhaustive()
+    }
+}
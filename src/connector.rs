@@ -1,23 +1,36 @@
 use std::{
-    fmt::Display,
+    fmt::{Debug, Display},
     future::Future,
-    sync::{Arc, RwLock},
+    sync::Arc,
 };
 
-use reqwest::{Method, header::HeaderMap};
-use serde::{Deserialize, Serialize};
+use reqwest::{
+    Client, Method,
+    header::{COOKIE, HeaderMap, HeaderName, HeaderValue},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::sync::{Mutex, Semaphore};
 
 use crate::{
+    circuit_breaker::CircuitBreaker,
     connector_builder::ApiBuilder,
-    error::Result,
+    digest_auth::DigestAuth,
+    error::{ApiError, Result},
+    error_decoder::{ErrorDecoder, JsonErrorDecoder},
     filter::{Filter, FilterRule},
+    oauth1::OAuth1,
+    oauth2::TokenSource,
     pagination::{Pagination, PaginationRule, RequestPagination},
     query::Query,
     range::{Range, RangeRule},
+    rate_limit_bucket::RateLimitBuckets,
+    rate_limit_strategy::RateLimitStrategy,
     rate_limiter::{RateLimiter, TimePeriod},
     request::Request,
     request_builder::RequestBuilder,
+    request_signer::RequestSigner,
     request_url::RequestUrl,
+    retry_policy::RetryPolicy,
     sort::{Sort, SortRule},
 };
 
@@ -33,9 +46,9 @@ pub enum AuthorizationType {
     // `token` into request headers
     // `Authorization: Bearer <token>`
     Bearer(String),
-    // `api_key` into request headers
-    // `X-API-Key: 1234567890abcdef`
-    ApiKey(String),
+    // `api_key` into a request header, a query parameter or a cookie
+    // `X-API-Key: 1234567890abcdef` by default
+    ApiKey(String, ApiKeyLocation),
     // `access_token` into request headers
     // `Authorization: Bearer <access_token>`
     // `refresh_token` into request headers
@@ -43,6 +56,12 @@ pub enum AuthorizationType {
     OAuth2(String),
     Keycloak(Box<AuthorizationType>),
     OIDC(String),
+    // Every attempt is signed with the client and token credentials
+    // `Authorization: OAuth oauth_consumer_key="...", oauth_signature="..."`
+    OAuth1(OAuth1),
+    // `username:password` answering the `WWW-Authenticate: Digest` challenge of the server
+    // `Authorization: Digest username="...", nonce="...", response="..."`
+    Digest(DigestAuth),
 }
 
 impl AuthorizationType {
@@ -52,37 +71,84 @@ impl AuthorizationType {
     /// * `headers` - A mutable reference to the request headers
     pub fn header_value(&self, headers: &mut HeaderMap) -> Result<()> {
         match self {
-            AuthorizationType::None => {}
-            AuthorizationType::ApiKey(_) => {
-                headers.insert(
-                    "X-API-Key",
-                    reqwest::header::HeaderValue::from_str(&self.to_string())?,
-                );
+            // The header is set for each attempt
+            AuthorizationType::None
+            | AuthorizationType::OAuth1(_)
+            | AuthorizationType::Digest(_) => {}
+            AuthorizationType::Keycloak(auth_type) => auth_type.header_value(headers)?,
+            AuthorizationType::ApiKey(_, ApiKeyLocation::Header(name)) => {
+                headers.insert(HeaderName::from_bytes(name.as_bytes())?, self.try_header()?);
+            }
+            AuthorizationType::ApiKey(key, ApiKeyLocation::Cookie(name)) => {
+                headers.append(COOKIE, HeaderValue::from_str(&format!("{name}={key}"))?);
             }
+            // The query parameter is added by `RequestUrl::as_url`, to every page
+            AuthorizationType::ApiKey(_, ApiKeyLocation::Query(_)) => {}
             _ => {
-                headers.insert(
-                    reqwest::header::AUTHORIZATION,
-                    reqwest::header::HeaderValue::from_str(&self.to_string())?,
-                );
+                headers.insert(reqwest::header::AUTHORIZATION, self.try_header()?);
             }
         }
 
         Ok(())
     }
+
+    /// Name and value of the API key sent as a query parameter
+    pub(crate) fn query_api_key(&self) -> Option<(&str, &str)> {
+        match self {
+            AuthorizationType::ApiKey(key, ApiKeyLocation::Query(name)) => Some((name, key)),
+            AuthorizationType::Keycloak(auth_type) => auth_type.query_api_key(),
+            _ => None,
+        }
+    }
+
+    /// Value of the authorization header
+    ///
+    /// Returns `ApiError::AuthorizationType` for `AuthorizationType::None`
+    pub fn try_header(&self) -> Result<HeaderValue> {
+        match self {
+            AuthorizationType::None
+            | AuthorizationType::OAuth1(_)
+            | AuthorizationType::Digest(_) => Err(ApiError::AuthorizationType),
+            AuthorizationType::Keycloak(auth_type) => auth_type.try_header(),
+            _ => Ok(HeaderValue::from_str(&self.to_string())?),
+        }
+    }
+}
+
+/// Location of the API key in the requests
+///
+/// # Variants
+/// * Header - Header with the given name (Default = `X-API-Key`)
+/// * Query - Query parameter with the given name, kept on every page and redacted from the logs and errors
+/// * Cookie - Cookie with the given name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeyLocation {
+    Header(String),
+    Query(String),
+    Cookie(String),
+}
+
+impl Default for ApiKeyLocation {
+    fn default() -> Self {
+        ApiKeyLocation::Header(String::from("X-API-Key"))
+    }
 }
 
+/// `None`, `OAuth1` and `Digest` are displayed as an empty string, [AuthorizationType::try_header] rejects them
 impl Display for AuthorizationType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AuthorizationType::Basic(token) => write!(f, "Basic {token}"),
-            AuthorizationType::ApiKey(token) => write!(f, "{token}"),
+            AuthorizationType::ApiKey(token, _) => write!(f, "{token}"),
             AuthorizationType::Bearer(token)
             | AuthorizationType::OAuth2(token)
             | AuthorizationType::OIDC(token) => {
                 write!(f, "Bearer {token}")
             }
             AuthorizationType::Keycloak(auth_type) => write!(f, "{auth_type}"),
-            _ => panic!("TokenType::None is not allowed"),
+            AuthorizationType::None
+            | AuthorizationType::OAuth1(_)
+            | AuthorizationType::Digest(_) => Ok(()),
         }
     }
 }
@@ -96,6 +162,14 @@ impl Display for AuthorizationType {
 /// * authorization - Authorization type to be used in the request
 /// * endpoint - API endpoint to be used in the request
 /// * pagination - Pagination type to be used in the request
+/// * retry_policy - Policy to retry the failed attempts of the requests
+/// * idempotency_header - Header carrying the idempotency key of the retried POST and PATCH requests
+/// * client - HTTP client shared by every request of the API
+/// * concurrency - Semaphore limiting the requests in flight
+/// * circuit_breaker - Circuit breaker with one circuit per host
+/// * error_decoder - Decoder of the error responses, returned as `ApiError::Api`
+/// * token_source - Source of the access token, renewed before it expires
+/// * signer - Signer of the requests, such as AWS Signature Version 4
 #[derive(Debug, Clone)]
 pub struct Api<
     P: Pagination = RequestPagination,
@@ -111,8 +185,15 @@ pub struct Api<
     pub(crate) filter: F,
     pub(crate) sort: S,
     pub(crate) range: R,
-    pub(crate) rate_limit: Arc<RwLock<RateLimiter>>,
-    pub(crate) force_limit: Option<u8>,
+    pub(crate) rate_limit: RateLimitBuckets,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) idempotency_header: HeaderName,
+    pub(crate) client: Client,
+    pub(crate) concurrency: Option<Arc<Semaphore>>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) error_decoder: Option<Arc<dyn ErrorDecoder>>,
+    pub(crate) token_source: Option<Arc<TokenSource>>,
+    pub(crate) signer: Option<Arc<dyn RequestSigner>>,
 }
 
 impl<P: Pagination, F: Filter, S: Sort, R: Range> Api<P, F, S, R>
@@ -138,6 +219,26 @@ where
         self.authorization.to_string()
     }
 
+    /// Getter for the token source of the API
+    pub fn token_source(&self) -> Option<&TokenSource> {
+        self.token_source.as_deref()
+    }
+
+    /// Getter for the HTTP client shared by every request of the API
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Getter for the circuit breaker of the API, to check the state of its hosts
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.circuit_breaker.as_ref()
+    }
+
+    /// Getter for the rate limit buckets of the API
+    pub fn rate_limit_buckets(&self) -> &RateLimitBuckets {
+        &self.rate_limit
+    }
+
     /// Setter for the filter pattern
     ///
     /// Set the pattern to match the filter\
@@ -224,28 +325,80 @@ where
     }
 
     /// Set the rate limit for the API
+    ///
+    /// Routes matching a rate limit bucket keep the limit of their bucket
     pub fn rate_limit(self, rate_limit: u32) -> Self {
-        match self.rate_limit.write() {
-            Ok(mut rate) => rate.limit = rate_limit,
-            Err(e) => log::error!("Rate limiter error: {e:?}"),
-        }
-        self
+        self.configure_rate_limiter(|limiter| limiter.limit = rate_limit)
     }
 
     /// Set the rate period for the API
     pub fn rate_period(self, rate_period: TimePeriod) -> Self {
-        match self.rate_limit.write() {
-            Ok(mut rate) => rate.period = rate_period,
-            Err(e) => log::error!("Rate limiter error: {e:?}"),
-        }
+        self.configure_rate_limiter(|limiter| limiter.period = rate_period)
+    }
+
+    /// Set the algorithm used by the rate limiter of the API
+    pub fn rate_strategy(self, strategy: impl RateLimitStrategy + 'static) -> Self {
+        self.configure_rate_limiter(|limiter| limiter.strategy = Box::new(strategy))
+    }
+
+    /// Apply the change to the rate limiter of the API
+    ///
+    /// The requests waiting for the rate limit do not hold the limiter.
+    /// If it is locked by a guard held elsewhere, this API gets a new limiter with the change
+    fn configure_rate_limiter(mut self, configure: impl FnOnce(&mut RateLimiter)) -> Self {
+        let pending = match self.rate_limit.fallback.try_lock() {
+            Ok(mut limiter) => {
+                configure(&mut limiter);
+                None
+            }
+            Err(_) => Some(configure),
+        };
+        let Some(configure) = pending else {
+            return self;
+        };
+        log::warn!("Rate limiter is locked, a new rate limiter is used by the API");
+        let mut limiter = RateLimiter::default();
+        configure(&mut limiter);
+        self.rate_limit.fallback = Arc::new(Mutex::new(limiter));
+        self
+    }
+
+    /// Update the rate limiter shared by this API and its clones
+    ///
+    /// Waits for the limiter to be free, the requests waiting for the rate limit do not hold it
+    pub async fn update_rate_limiter(&self, update: impl FnOnce(&mut RateLimiter)) {
+        update(&mut *self.rate_limit.fallback.lock().await);
+    }
+
+    /// Set the policy to retry the failed attempts of the requests
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
         self
     }
 
     /// Set the number of retry attempts on a 429 response
     ///
     /// None will not retry
+    #[deprecated(note = "use `retry_policy`")]
     pub fn force_limit(mut self, limit: Option<u8>) -> Self {
-        self.force_limit = limit;
+        self.retry_policy = RetryPolicy::force_limit(limit);
+        self
+    }
+
+    /// Set the decoder of the error responses
+    pub fn error_decoder(mut self, decoder: impl ErrorDecoder + 'static) -> Self {
+        self.error_decoder = Some(Arc::new(decoder));
+        self
+    }
+
+    /// Decode the error responses as JSON into `E`
+    pub fn error_body<E: DeserializeOwned + Debug + Send + Sync + 'static>(self) -> Self {
+        self.error_decoder(JsonErrorDecoder::<E>::new())
+    }
+
+    /// Set the header carrying the idempotency key of the retried POST and PATCH requests
+    pub fn idempotency_header(mut self, name: HeaderName) -> Self {
+        self.idempotency_header = name;
         self
     }
 }
@@ -275,21 +428,49 @@ where
     }
 
     api.authorization.header_value(&mut headers)?;
+    let signer = match (&api.authorization, &api.signer) {
+        (AuthorizationType::OAuth1(oauth1), None) => {
+            Some(Arc::new(oauth1.clone()) as Arc<dyn RequestSigner>)
+        }
+        (AuthorizationType::OAuth1(_), Some(_)) => {
+            return Err(ApiError::RequestSigning(String::from(
+                "OAuth 1.0a cannot be combined with another request signer",
+            )));
+        }
+        _ => api.signer.clone(),
+    };
+    let digest = match &api.authorization {
+        AuthorizationType::Digest(digest) => Some(Arc::new(digest.clone())),
+        _ => None,
+    };
+
+    let route = route.to_string();
+    let rate_limiter = api.rate_limit.limiter_for(&route);
+    let mut url = RequestUrl::new(&api.endpoint)
+        .route(route)
+        .method(method.clone());
+    if let Some((name, key)) = api.authorization.query_api_key() {
+        url = url.api_key(name, key);
+    }
 
-    let url = RequestUrl::new(&api.endpoint)
-        .route(route.to_string())
-        .method(Method::GET);
-
-    Ok(
-        RequestBuilder::<X, B, P, F, S, R>::new(url, api.rate_limit.clone())
-            .headers(headers)
-            .pagination(api.pagination.pagination().clone())
-            .filter(api.filter.clone())
-            .sort(api.sort.clone())
-            .range(api.range.clone())
-            .force_limit(api.force_limit)
-            .build(),
-    )
+    Ok(RequestBuilder::<X, B, P, F, S, R>::new(url, rate_limiter)
+        .method(method)
+        .rate_limit_buckets(api.rate_limit.clone())
+        .headers(headers)
+        .pagination(api.pagination.pagination().clone())
+        .filter(api.filter.clone())
+        .sort(api.sort.clone())
+        .range(api.range.clone())
+        .retry_policy(api.retry_policy.clone())
+        .idempotency_header(api.idempotency_header.clone())
+        .client(api.client.clone())
+        .concurrency(api.concurrency.clone())
+        .circuit_breaker(api.circuit_breaker.clone())
+        .error_decoder(api.error_decoder.clone())
+        .token_source(api.token_source.clone())
+        .signer(signer)
+        .digest(digest)
+        .build())
 }
 
 impl<P: Pagination, F: Filter, S: Sort, R: Range> Connector<P, F, S, R> for Api<P, F, S, R>
@@ -1,12 +1,27 @@
-use std::sync::{Arc, RwLock};
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use reqwest::{Client, header::HeaderName};
+use serde::de::DeserializeOwned;
+use tokio::sync::{Mutex, Semaphore};
 
 use crate::{
-    connector::{Api, AuthorizationType},
+    aws_sigv4::AwsSigV4,
+    circuit_breaker::CircuitBreaker,
+    connector::{Api, ApiKeyLocation, AuthorizationType},
+    digest_auth::DigestAuth,
+    error::{ApiError, Result},
+    error_decoder::{ErrorDecoder, JsonErrorDecoder},
     filter::{Filter, FilterRule},
+    oauth1::OAuth1,
+    oauth2::TokenSource,
     pagination::{Pagination, PaginationRule, RequestPagination},
     query::Query,
     range::{Range, RangeRule},
-    rate_limiter::{RateLimiter, TimePeriod},
+    rate_limit_bucket::{RateLimitBucket, RateLimitBuckets},
+    rate_limit_strategy::RateLimitStrategy,
+    rate_limiter::{RateLimitTier, RateLimiter, TimePeriod},
+    request_signer::RequestSigner,
+    retry_policy::{IDEMPOTENCY_KEY, RetryPolicy},
     sort::{Sort, SortRule},
 };
 
@@ -42,7 +57,18 @@ pub struct ApiBuilder<
     pub(crate) sort: S,
     pub(crate) range: R,
     pub(crate) rate_limiter: RateLimiter,
-    pub(crate) force_limit: Option<u8>,
+    pub(crate) rate_limit_buckets: Vec<RateLimitBucket>,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) idempotency_header: HeaderName,
+    pub(crate) client: Option<Client>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+    pub(crate) tcp_keepalive: Option<Duration>,
+    pub(crate) max_concurrency: Option<usize>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) error_decoder: Option<Arc<dyn ErrorDecoder>>,
+    pub(crate) token_source: Option<Arc<TokenSource>>,
+    pub(crate) signer: Option<Arc<dyn RequestSigner>>,
 }
 
 impl<P: Pagination, F: Filter, S: Sort, R: Range> ApiBuilder<P, F, S, R>
@@ -59,7 +85,18 @@ where
     /// * sort - S::default()
     /// * range - R::default()
     /// * rate_limiter - RateLimiter::new(1, TimePeriod::Second)
-    /// * force_limit - None
+    /// * rate_limit_buckets - Empty (Every route uses the rate_limiter)
+    /// * retry_policy - RetryPolicy::default() (No retry)
+    /// * idempotency_header - Idempotency-Key
+    /// * client - None (A client is built from the pool settings)
+    /// * pool_idle_timeout - None (reqwest default)
+    /// * pool_max_idle_per_host - None (reqwest default)
+    /// * tcp_keepalive - None (reqwest default)
+    /// * max_concurrency - None (No limit on the requests in flight)
+    /// * circuit_breaker - None
+    /// * error_decoder - None (Error responses keep the raw text of their body)
+    /// * token_source - None
+    /// * signer - None
     pub fn new(endpoint: impl ToString) -> Self {
         Self {
             authorization: AuthorizationType::None,
@@ -69,7 +106,18 @@ where
             sort: S::default(),
             range: R::default(),
             rate_limiter: RateLimiter::new(1, TimePeriod::Second),
-            force_limit: None,
+            rate_limit_buckets: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            idempotency_header: IDEMPOTENCY_KEY,
+            client: None,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            tcp_keepalive: None,
+            max_concurrency: None,
+            circuit_breaker: None,
+            error_decoder: None,
+            token_source: None,
+            signer: None,
         }
     }
 
@@ -93,16 +141,81 @@ where
         self
     }
 
+    /// Send the API key in the `X-API-Key` header
     pub fn api_key(mut self, token: impl ToString) -> Self {
-        self.authorization = AuthorizationType::ApiKey(token.to_string());
+        self.authorization =
+            AuthorizationType::ApiKey(token.to_string(), ApiKeyLocation::default());
+        self
+    }
+
+    /// Send the API key in a custom header, a query parameter or a cookie
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let api: Api = ApiBuilder::new("https://api.example.com")
+    ///     .api_key_in("1234567890abcdef", ApiKeyLocation::Query(String::from("apikey")))
+    ///     .build();
+    /// ```
+    pub fn api_key_in(mut self, token: impl ToString, location: ApiKeyLocation) -> Self {
+        self.authorization = AuthorizationType::ApiKey(token.to_string(), location);
         self
     }
 
+    /// Get the access token of every request from a token source
+    ///
+    /// The token is renewed before it expires,
+    /// and a request rejected with a 401 is sent once more with a new token
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let client = OAuth2Client::new("https://auth.example.com/token", "client_id")
+    ///     .client_secret("client_secret");
+    /// let api = ApiBuilder::new("https://api.example.com")
+    ///     .token_source(TokenSource::new(client, Grant::ClientCredentials))
+    ///     .build();
+    /// ```
+    pub fn token_source(mut self, source: TokenSource) -> Self {
+        self.token_source = Some(Arc::new(source));
+        self
+    }
+
+    /// Sign every request with the signer
+    ///
+    /// The signature is computed again for every attempt and every page
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let api: Api = ApiBuilder::new("https://api.example.com")
+    ///     .signer(HmacSigner::new("secret").key(Placement::Header(String::from("X-Api-Key")), "key"))
+    ///     .build();
+    /// ```
+    pub fn signer(mut self, signer: impl RequestSigner + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Sign every request with AWS Signature Version 4
+    pub fn aws_sigv4(self, signer: AwsSigV4) -> Self {
+        self.signer(signer)
+    }
+
     pub fn oidc(mut self, token: impl ToString) -> Self {
         self.authorization = AuthorizationType::OIDC(token.to_string());
         self
     }
 
+    /// Sign every request with OAuth 1.0a
+    pub fn oauth1(mut self, oauth1: OAuth1) -> Self {
+        self.authorization = AuthorizationType::OAuth1(oauth1);
+        self
+    }
+
+    /// Answer the Digest challenges of the server with the credentials
+    pub fn digest(mut self, digest: DigestAuth) -> Self {
+        self.authorization = AuthorizationType::Digest(digest);
+        self
+    }
+
     pub fn keycloak(mut self, auth_type: AuthorizationType) -> Self {
         self.authorization = AuthorizationType::Keycloak(Box::new(auth_type));
         self
@@ -138,12 +251,190 @@ where
         self
     }
 
+    /// Add a limit enforced along with `limit` and `limit_period`
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // 10 requests per second and 10 000 requests per day
+    /// let api = ApiBuilder::new("https://api.example.com")
+    ///     .limit(10)
+    ///     .limit_period(TimePeriod::Second)
+    ///     .limit_tier(10_000, TimePeriod::Day)
+    ///     .build();
+    /// ```
+    pub fn limit_tier(mut self, limit: u32, period: impl Into<TimePeriod>) -> Self {
+        self.rate_limiter
+            .tiers
+            .push(RateLimitTier::new(limit, period));
+        self
+    }
+
+    /// Set the algorithm used by the rate limiter (Default = [FixedWindow](crate::rate_limit_strategy::FixedWindow))
+    pub fn limit_strategy(mut self, strategy: impl RateLimitStrategy + 'static) -> Self {
+        self.rate_limiter.strategy = Box::new(strategy);
+        self
+    }
+
+    /// Register a rate limiter for the routes matching the pattern
+    ///
+    /// The pattern is a route prefix, where `*` matches any single path segment.\
+    /// Buckets are checked in the order they are registered,
+    /// and the routes matching no bucket use the API rate limiter (`limit` and `limit_period`).\
+    /// The name is used to update the bucket reported by the server
+    /// in the `X-RateLimit-Resource` or `RateLimit-Policy` headers.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let api = ApiBuilder::new("https://api.github.com")
+    ///     .limit(5000)
+    ///     .limit_period(TimePeriod::Hour)
+    ///     .limit_bucket("search", "/search", RateLimiter::new(30, TimePeriod::Minute))
+    ///     .build();
+    /// ```
+    pub fn limit_bucket(
+        mut self,
+        name: impl ToString,
+        pattern: impl ToString,
+        limiter: RateLimiter,
+    ) -> Self {
+        self.rate_limit_buckets
+            .push(RateLimitBucket::new(name, pattern, limiter));
+        self
+    }
+
+    /// Set the policy to retry the failed attempts of the requests
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let api = ApiBuilder::new("https://api.example.com")
+    ///     .retry_policy(RetryPolicy::new(3).max_backoff(Duration::from_secs(5)))
+    ///     .build();
+    /// ```
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Retry the 429 responses up to `limit` times
+    #[deprecated(note = "use `retry_policy`")]
     pub fn force_limit(mut self, limit: u8) -> Self {
-        self.force_limit = Some(limit);
+        self.retry_policy = RetryPolicy::force_limit(Some(limit));
         self
     }
 
+    /// Set the header carrying the idempotency key of the retried POST and PATCH requests
+    ///
+    /// The key is only sent when the retry policy retries these requests
+    pub fn idempotency_header(mut self, name: HeaderName) -> Self {
+        self.idempotency_header = name;
+        self
+    }
+
+    /// Use an existing client for every request of the API
+    ///
+    /// The pool settings of the builder are ignored when a client is provided
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Set how long an idle connection is kept in the pool
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum number of idle connections kept per host
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Set the TCP keep-alive interval of the connections
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Limit the number of requests in flight at the same time
+    ///
+    /// Every request and every page of a paginated request spawned from the API
+    /// waits for a free slot before being sent.\
+    /// A limit of 0 would block every request, it is raised to 1
+    pub fn max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = Some(max.max(1));
+        self
+    }
+
+    /// Fail fast while a host of the API is down
+    ///
+    /// Requests to a host whose circuit is open return `ApiError::CircuitOpen`
+    /// without being sent, until the cool-down of the circuit breaker is over
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Decode the error responses with a decoder
+    ///
+    /// Decoded errors are returned as `ApiError::Api`,
+    /// and the errors that cannot be decoded keep the raw text of their body in `ApiError::Http`
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let api = ApiBuilder::new("https://api.example.com")
+    ///     .error_decoder(ProblemDetailsDecoder)
+    ///     .build();
+    /// ```
+    pub fn error_decoder(mut self, decoder: impl ErrorDecoder + 'static) -> Self {
+        self.error_decoder = Some(Arc::new(decoder));
+        self
+    }
+
+    /// Decode the error responses as JSON into `E`
+    pub fn error_body<E: DeserializeOwned + Debug + Send + Sync + 'static>(self) -> Self {
+        self.error_decoder(JsonErrorDecoder::<E>::new())
+    }
+
+    /// Build the client shared by every request of the API
+    fn build_client(&self) -> Result<Client> {
+        if let Some(client) = &self.client {
+            return Ok(client.clone());
+        }
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        builder.build().map_err(ApiError::ReqwestBuilder)
+    }
+
+    /// Build the API
+    ///
+    /// If the HTTP client cannot be built, the error is logged and a default client is used,
+    /// without the pool and keep-alive settings. Use [ApiBuilder::try_build] to get the error instead
     pub fn build(self) -> Api<P, F, S, R> {
+        let client = self.build_client().unwrap_or_else(|e| {
+            log::error!("Client builder error, using a default client: {e:?}");
+            Client::new()
+        });
+        self.with_client(client)
+    }
+
+    /// Build the API
+    ///
+    /// Returns `ApiError::ReqwestBuilder` if the HTTP client cannot be built with the pool and keep-alive settings
+    pub fn try_build(self) -> Result<Api<P, F, S, R>> {
+        let client = self.build_client()?;
+        Ok(self.with_client(client))
+    }
+
+    fn with_client(self, client: Client) -> Api<P, F, S, R> {
         Api {
             authorization: self.authorization,
             endpoint: self.endpoint,
@@ -151,8 +442,20 @@ where
             filter: self.filter,
             sort: self.sort,
             range: self.range,
-            rate_limit: Arc::new(RwLock::new(self.rate_limiter)),
-            force_limit: self.force_limit,
+            rate_limit: RateLimitBuckets::new(
+                self.rate_limit_buckets,
+                Arc::new(Mutex::new(self.rate_limiter)),
+            ),
+            retry_policy: self.retry_policy,
+            idempotency_header: self.idempotency_header,
+            client,
+            concurrency: self
+                .max_concurrency
+                .map(|max| Arc::new(Semaphore::new(max))),
+            circuit_breaker: self.circuit_breaker,
+            error_decoder: self.error_decoder,
+            token_source: self.token_source,
+            signer: self.signer,
         }
     }
 }
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{
+    connector::Api,
+    connector_builder::ApiBuilder,
+    error::{ApiError, Result},
+    filter::Filter,
+    oauth2::{Grant, OAuth2Client, OAuth2Error, TokenSource},
+    pagination::Pagination,
+    query::Query,
+    range::Range,
+    sort::Sort,
+};
+
+/// Interval between two polls of the token endpoint when the server does not give one
+const DEFAULT_POLL_INTERVAL: u64 = 5;
+
+/// Time added to the poll interval when the server answers `slow_down`
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+
+/// Response of the device authorization endpoint (RFC 8628 section 3.2)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    #[serde(default)]
+    pub interval: Option<u64>,
+}
+
+impl DeviceAuthorization {
+    /// Log the verification URI and the user code at the info level
+    ///
+    /// To show them in a terminal, give your own callback to [DeviceCodeFlow::connect]:
+    /// ```rust,ignore
+    /// flow.connect(url, |device| eprintln!("Open {} and enter {}", device.verification_uri, device.user_code))
+    /// ```
+    pub fn log(&self) {
+        match &self.verification_uri_complete {
+            Some(uri) => log::info!("Open {uri} to authorize this device"),
+            None => log::info!(
+                "Open {} and enter the code {} to authorize this device",
+                self.verification_uri,
+                self.user_code
+            ),
+        }
+    }
+}
+
+/// OAuth2 device authorization grant (RFC 8628)
+///
+/// The user code and the verification URI are given to a callback, to show them to the user,
+/// who approves the device from another browser.\
+/// Meanwhile, the token endpoint is polled every `interval` seconds, slower after a `slow_down`,
+/// until the user approves or denies the device, or the device code expires.
+///
+/// # Attributes
+/// * client - Client of the token endpoint, with the scopes to request
+/// * device_authorization_endpoint - URL of the device authorization endpoint
+///
+/// # Example
+/// ```rust,ignore
+/// let client = OAuth2Client::new("https://auth.example.com/token", "cli").scopes(["read"]);
+/// let api: Api = DeviceCodeFlow::new(client, "https://auth.example.com/device")
+///     .connect("https://api.example.com", |device| {
+///         eprintln!("Open {} and enter {}", device.verification_uri, device.user_code)
+///     })
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeviceCodeFlow {
+    pub(crate) client: OAuth2Client,
+    pub(crate) device_authorization_endpoint: String,
+}
+
+impl DeviceCodeFlow {
+    pub fn new(client: OAuth2Client, device_authorization_endpoint: impl ToString) -> Self {
+        Self {
+            client,
+            device_authorization_endpoint: device_authorization_endpoint.to_string(),
+        }
+    }
+
+    /// Request a device code and a user code from the device authorization endpoint
+    pub async fn request_code(&self) -> Result<DeviceAuthorization> {
+        let mut params = Vec::new();
+        if !self.client.scopes.is_empty() {
+            params.push(("scope", self.client.scopes.join(" ")));
+        }
+        params.extend(self.client.audience_params());
+        self.client
+            .post_form(&self.device_authorization_endpoint, params)
+            .await
+    }
+
+    /// Poll the token endpoint until the user approves the device
+    ///
+    /// Returns `ApiError::OAuth2Flow` if the user denies the device or the device code expires
+    pub async fn poll(&self, authorization: &DeviceAuthorization) -> Result<TokenSource> {
+        let grant = Grant::DeviceCode(authorization.device_code.clone());
+        let expires_at = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval =
+            Duration::from_secs(authorization.interval.unwrap_or(DEFAULT_POLL_INTERVAL));
+        loop {
+            tokio::time::sleep(interval).await;
+            if Instant::now() >= expires_at {
+                return Err(ApiError::OAuth2Flow(String::from("Device code expired")));
+            }
+            let error = match self.client.request_token(&grant).await {
+                Ok(response) => {
+                    return Ok(TokenSource::with_token(
+                        self.client.clone(),
+                        grant,
+                        response,
+                    ));
+                }
+                Err(error) => error,
+            };
+            match error.api_body::<OAuth2Error>().map(|e| e.error.as_str()) {
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += SLOW_DOWN_INCREMENT,
+                Some("access_denied") => {
+                    return Err(ApiError::OAuth2Flow(String::from("Authorization denied")));
+                }
+                Some("expired_token") => {
+                    return Err(ApiError::OAuth2Flow(String::from("Device code expired")));
+                }
+                _ => return Err(error),
+            }
+        }
+    }
+
+    /// Run the flow and return the token source of the user
+    ///
+    /// `show` is called with the user code and the verification URI, to show them to the user
+    pub async fn authorize(&self, show: impl FnOnce(&DeviceAuthorization)) -> Result<TokenSource> {
+        let authorization = self.request_code().await?;
+        show(&authorization);
+        self.poll(&authorization).await
+    }
+
+    /// Run the flow and build an API using the token of the user
+    pub async fn connect<P: Pagination, F: Filter, S: Sort, R: Range>(
+        &self,
+        url: impl ToString,
+        show: impl FnOnce(&DeviceAuthorization),
+    ) -> Result<Api<P, F, S, R>>
+    where
+        Query: for<'a> From<&'a F> + for<'a> From<&'a S> + for<'a> From<&'a R>,
+    {
+        let source = self.authorize(show).await?;
+        let token = source.token().await?;
+        Ok(ApiBuilder::new(url)
+            .oauth2(token)
+            .token_source(source)
+            .build())
+    }
+}
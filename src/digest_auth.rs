@@ -0,0 +1,304 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use md5::Md5;
+use reqwest::{
+    Method,
+    header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE},
+};
+use sha2::{Digest, Sha256};
+use url::{Position, Url};
+
+use crate::error::Result;
+
+/// Hash algorithm of a Digest challenge (RFC 7616 section 3.3)
+///
+/// The `-sess` variants hash the client nonce into the credentials of the session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display, strum::EnumString)]
+pub enum DigestAlgorithm {
+    #[default]
+    #[strum(serialize = "MD5", ascii_case_insensitive)]
+    Md5,
+    #[strum(serialize = "MD5-sess", ascii_case_insensitive)]
+    Md5Sess,
+    #[strum(serialize = "SHA-256", ascii_case_insensitive)]
+    Sha256,
+    #[strum(serialize = "SHA-256-sess", ascii_case_insensitive)]
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    fn hash(&self, data: impl AsRef<[u8]>) -> String {
+        let bytes = match self {
+            DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => Md5::digest(data).to_vec(),
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => Sha256::digest(data).to_vec(),
+        };
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn is_session(&self) -> bool {
+        matches!(self, DigestAlgorithm::Md5Sess | DigestAlgorithm::Sha256Sess)
+    }
+}
+
+/// Quality of protection of a Digest challenge
+///
+/// # Variants
+/// * Auth - Authentication of the method and the URI
+/// * AuthInt - Authentication of the method, the URI and the body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum DigestQop {
+    #[strum(serialize = "auth")]
+    Auth,
+    #[strum(serialize = "auth-int")]
+    AuthInt,
+}
+
+/// Challenge of a `WWW-Authenticate: Digest` header
+///
+/// # Attributes
+/// * realm - Protection space of the credentials
+/// * nonce - Nonce generated by the server
+/// * opaque - Data returned unchanged to the server
+/// * algorithm - Hash algorithm (Default = MD5)
+/// * qop - Quality of protection, None for the RFC 2069 servers
+/// * stale - The previous nonce expired, but the credentials were valid
+/// * cnonce - Client nonce, the same for every request of the challenge
+/// * nonce_count - Number of requests sent with the nonce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestChallenge {
+    pub(crate) realm: String,
+    pub(crate) nonce: String,
+    pub(crate) opaque: Option<String>,
+    pub(crate) algorithm: DigestAlgorithm,
+    pub(crate) qop: Option<DigestQop>,
+    pub(crate) stale: bool,
+    pub(crate) cnonce: String,
+    pub(crate) nonce_count: u32,
+}
+
+impl DigestChallenge {
+    /// Parse a `WWW-Authenticate` header value
+    ///
+    /// Returns None if it is not a Digest challenge, or if its algorithm is not supported
+    pub fn parse(header: &str) -> Option<Self> {
+        let (scheme, params) = header.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("Digest") {
+            return None;
+        }
+        let params = parse_params(params);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+        let algorithm = match param("algorithm") {
+            Some(algorithm) => algorithm.parse().ok()?,
+            None => DigestAlgorithm::Md5,
+        };
+        let qops = param("qop").unwrap_or_default();
+        let qops: Vec<&str> = qops.split(',').map(str::trim).collect();
+        let qop = if qops.contains(&"auth") {
+            Some(DigestQop::Auth)
+        } else if qops.contains(&"auth-int") {
+            Some(DigestQop::AuthInt)
+        } else {
+            None
+        };
+        Some(Self {
+            realm: param("realm").unwrap_or_default(),
+            nonce: param("nonce")?,
+            opaque: param("opaque"),
+            algorithm,
+            qop,
+            stale: param("stale").is_some_and(|stale| stale.eq_ignore_ascii_case("true")),
+            cnonce: uuid::Uuid::new_v4().simple().to_string(),
+            nonce_count: 0,
+        })
+    }
+
+    /// Strongest supported Digest challenge of the response headers
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(Self::parse)
+            .max_by_key(|challenge| match challenge.algorithm {
+                DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => 0,
+                DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => 1,
+            })
+    }
+
+    /// Set the client nonce, to reproduce a response
+    pub fn cnonce(mut self, cnonce: impl ToString) -> Self {
+        self.cnonce = cnonce.to_string();
+        self
+    }
+}
+
+/// Credentials of the HTTP Digest authentication (RFC 7616)
+///
+/// The first request is sent without credentials, and the 401 challenge of the server
+/// is answered by sending the request once more.\
+/// The challenge is shared by every request of the API, with its nonce count,
+/// and is renewed when the server marks the nonce as stale.
+///
+/// # Attributes
+/// * username - Name of the user
+/// * password - Password of the user
+/// * challenge - Last challenge of the server
+///
+/// # Example
+/// ```rust,ignore
+/// let api: Api = ApiBuilder::new("https://camera.local/api")
+///     .digest(DigestAuth::new("admin", "password"))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct DigestAuth {
+    pub(crate) username: String,
+    pub(crate) password: String,
+    pub(crate) challenge: Arc<Mutex<Option<DigestChallenge>>>,
+}
+
+impl DigestAuth {
+    pub fn new(username: impl ToString, password: impl ToString) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+            challenge: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn challenge(&self) -> MutexGuard<'_, Option<DigestChallenge>> {
+        self.challenge
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Answer the challenge for the following requests
+    pub fn set_challenge(&self, challenge: DigestChallenge) {
+        *self.challenge() = Some(challenge);
+    }
+
+    /// Value of the `Authorization` header of a request, with the next nonce count
+    ///
+    /// Returns None until the server sent a challenge
+    pub fn authorization(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: &[u8],
+    ) -> Result<Option<HeaderValue>> {
+        let mut challenge = self.challenge();
+        let Some(challenge) = challenge.as_mut() else {
+            return Ok(None);
+        };
+        challenge.nonce_count += 1;
+        let uri = &url[Position::BeforePath..Position::AfterQuery];
+        let header = self.header(challenge, method, uri, body);
+        Ok(Some(HeaderValue::from_str(&header)?))
+    }
+
+    /// `Digest` credentials answering the challenge (RFC 7616 section 3.4)
+    fn header(
+        &self,
+        challenge: &DigestChallenge,
+        method: &Method,
+        uri: &str,
+        body: &[u8],
+    ) -> String {
+        let algorithm = challenge.algorithm;
+        let nonce_count = format!("{:08x}", challenge.nonce_count);
+        let mut ha1 = algorithm.hash(format!(
+            "{}:{}:{}",
+            self.username, challenge.realm, self.password
+        ));
+        if algorithm.is_session() {
+            ha1 = algorithm.hash(format!("{ha1}:{}:{}", challenge.nonce, challenge.cnonce));
+        }
+        let ha2 = match challenge.qop {
+            Some(DigestQop::AuthInt) => {
+                algorithm.hash(format!("{method}:{uri}:{}", algorithm.hash(body)))
+            }
+            _ => algorithm.hash(format!("{method}:{uri}")),
+        };
+        let response = match challenge.qop {
+            Some(qop) => algorithm.hash(format!(
+                "{ha1}:{}:{nonce_count}:{}:{qop}:{ha2}",
+                challenge.nonce, challenge.cnonce
+            )),
+            None => algorithm.hash(format!("{ha1}:{}:{ha2}", challenge.nonce)),
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", uri=\"{uri}\", algorithm={algorithm}, nonce=\"{}\"",
+            quote(&self.username),
+            quote(&challenge.realm),
+            quote(&challenge.nonce)
+        );
+        if let Some(qop) = challenge.qop {
+            header.push_str(&format!(
+                ", nc={nonce_count}, cnonce=\"{}\", qop={qop}",
+                quote(&challenge.cnonce)
+            ));
+        }
+        header.push_str(&format!(", response=\"{response}\""));
+        if let Some(opaque) = &challenge.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+        }
+        header
+    }
+}
+
+/// Credentials are equal when the user and the password are, whatever the challenge
+impl PartialEq for DigestAuth {
+    fn eq(&self, other: &Self) -> bool {
+        self.username == other.username && self.password == other.password
+    }
+}
+
+/// The password is never printed
+impl std::fmt::Debug for DigestAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DigestAuth")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Split the `name=value` and `name="quoted, value"` parameters of a challenge
+fn parse_params(params: &str) -> Vec<(String, String)> {
+    let mut parsed = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        let name: String = std::iter::from_fn(|| chars.next_if(|c| *c != '=')).collect();
+        if chars.next().is_none() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+        } else {
+            value = std::iter::from_fn(|| chars.next_if(|c| *c != ','))
+                .collect::<String>()
+                .trim()
+                .to_string();
+        }
+        parsed.push((name.trim().to_string(), value));
+    }
+    parsed
+}
+
+/// Escape the quotes and backslashes of a quoted string
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,17 +1,38 @@
-use reqwest::header::InvalidHeaderValue;
+use std::{convert::Infallible, fmt::Display};
+
+use reqwest::{
+    Method, StatusCode,
+    header::{HeaderMap, InvalidHeaderName, InvalidHeaderValue},
+};
+
+use crate::{
+    error_decoder::{ErrorBody, ErrorDecoder},
+    request_url::redact_url,
+};
+
+/// Maximum size of the body kept in an [HttpError]
+pub const HTTP_ERROR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Maximum number of characters of the body excerpt kept in a [ParseError]
+pub const PARSE_ERROR_EXCERPT_LIMIT: usize = 256;
 
 #[derive(thiserror::Error, Debug)]
 pub enum ApiError {
     #[error("Wrong Authorization Type")]
     AuthorizationType,
+    #[deprecated(note = "HTTP errors are returned as `ApiError::Http`, use `ApiError::status()`")]
     #[error("Not Found")]
     NotFound,
+    #[deprecated(note = "HTTP errors are returned as `ApiError::Http`, use `ApiError::status()`")]
     #[error("Unauthorized")]
     Unauthorized,
+    #[deprecated(note = "HTTP errors are returned as `ApiError::Http`, use `ApiError::status()`")]
     #[error("Too Many Requests")]
     TooManyRequests,
+    #[deprecated(note = "HTTP errors are returned as `ApiError::Http`, use `ApiError::status()`")]
     #[error("Bad Request")]
     BadRequest,
+    #[deprecated(note = "HTTP errors are returned as `ApiError::Http`, use `ApiError::status()`")]
     #[error("Internal Server Error")]
     InternalServerError,
     #[error("Pagination Done")]
@@ -20,8 +41,14 @@ pub enum ApiError {
     PageLimitExceeded,
     #[error("JsonValue is not an Array")]
     JsonValueNotArray,
-    #[error("Response parse to T: {0}")]
-    ResponseParse(#[source] serde_json::Error),
+    #[error("Token response parse: {0}")]
+    TokenResponseParse(#[source] serde_json::Error),
+    #[error("Discovery document parse: {0}")]
+    DiscoveryParse(#[source] serde_json::Error),
+    #[error("Invalid Query: {0}")]
+    InvalidQuery(String),
+    #[error("Response parse to {0}")]
+    ResponseParse(#[source] Box<ParseError>),
     #[error("Response to text: {0}")]
     ResponseToText(#[source] reqwest::Error),
     #[error("ExecuteReqwest: {0}")]
@@ -36,22 +63,298 @@ pub enum ApiError {
     WrongUrlFormat(#[from] url::ParseError),
     #[error("Invalid Header Value: {0}")]
     InvalidHeaderValue(#[from] InvalidHeaderValue),
+    #[error("Invalid Header Name: {0}")]
+    InvalidHeaderName(#[from] InvalidHeaderName),
+    #[error("{0}")]
+    Http(Box<HttpError>),
+    #[error("{0}")]
+    Api(Box<ApiErrorResponse>),
+    #[error("OAuth2 flow: {0}")]
+    OAuth2Flow(String),
+    #[error("Client assertion: {0}")]
+    #[cfg(feature = "private-key-jwt")]
+    ClientAssertion(#[source] jsonwebtoken::errors::Error),
+    #[error("Request signing: {0}")]
+    RequestSigning(String),
+    #[error("Circuit Open: {0}")]
+    CircuitOpen(String),
     #[error("{1} ➤  {0}")]
     Connector(#[source] Box<ApiError>, ConnectorError),
 }
 
-impl From<reqwest::StatusCode> for ApiError {
-    fn from(status: reqwest::StatusCode) -> Self {
+impl From<Infallible> for ApiError {
+    fn from(error: Infallible) -> Self {
+        match error {}
+    }
+}
+
+/// Deprecated: HTTP errors are returned as `ApiError::Http`, use `ApiError::status()`
+#[allow(deprecated)]
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
         match status {
-            reqwest::StatusCode::NOT_FOUND => ApiError::NotFound,
-            reqwest::StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
-            reqwest::StatusCode::TOO_MANY_REQUESTS => ApiError::TooManyRequests,
-            reqwest::StatusCode::INTERNAL_SERVER_ERROR => ApiError::InternalServerError,
+            StatusCode::NOT_FOUND => ApiError::NotFound,
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => ApiError::TooManyRequests,
+            StatusCode::INTERNAL_SERVER_ERROR => ApiError::InternalServerError,
             _ => ApiError::BadRequest,
         }
     }
 }
 
+impl ApiError {
+    /// Status code of the response, if the error comes from one
+    #[allow(deprecated)]
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Http(error) => Some(error.status),
+            ApiError::Api(error) => Some(error.http.status),
+            ApiError::NotFound => Some(StatusCode::NOT_FOUND),
+            ApiError::Unauthorized => Some(StatusCode::UNAUTHORIZED),
+            ApiError::TooManyRequests => Some(StatusCode::TOO_MANY_REQUESTS),
+            ApiError::InternalServerError => Some(StatusCode::INTERNAL_SERVER_ERROR),
+            ApiError::Connector(error, _) => error.status(),
+            _ => None,
+        }
+    }
+
+    /// Decoded body of the error response, if it is a `E`
+    pub fn api_body<E: 'static>(&self) -> Option<&E> {
+        match self {
+            ApiError::Api(error) => error.body::<E>(),
+            ApiError::Connector(error, _) => error.api_body(),
+            _ => None,
+        }
+    }
+
+    /// Check if the server rejected the request (4xx)
+    pub fn is_client_error(&self) -> bool {
+        self.status().is_some_and(|status| status.is_client_error())
+    }
+
+    /// Check if the server failed to handle the request (5xx)
+    pub fn is_server_error(&self) -> bool {
+        self.status().is_some_and(|status| status.is_server_error())
+    }
+
+    /// Check if sending the request again may succeed
+    ///
+    /// True for timeouts, connection errors, 408, 429, 502, 503 and 504 responses
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::ReqwestExecute(e) => e.is_timeout() || e.is_connect(),
+            ApiError::CircuitOpen(_) => false,
+            ApiError::Connector(error, _) => error.is_retryable(),
+            error => matches!(
+                error.status(),
+                Some(
+                    StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::TOO_MANY_REQUESTS
+                        | StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                )
+            ),
+        }
+    }
+}
+
+/// Non-success response of the API
+///
+/// # Attributes
+/// * status - Status code of the response
+/// * headers - Headers of the response
+/// * body - Body of the response, truncated to `HTTP_ERROR_BODY_LIMIT` bytes
+/// * method - Method of the request
+/// * url - URL of the request, with its secrets redacted
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+    pub method: Method,
+    pub url: String,
+}
+
+impl HttpError {
+    /// Read the error from a response, keeping at most `HTTP_ERROR_BODY_LIMIT` bytes of its body
+    pub async fn from_response(method: Method, mut response: reqwest::Response) -> Self {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let url = redact_url(response.url());
+        let mut body = Vec::new();
+        while body.len() < HTTP_ERROR_BODY_LIMIT {
+            match response.chunk().await {
+                Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Error body read error: {e:?}");
+                    break;
+                }
+            }
+        }
+        body.truncate(HTTP_ERROR_BODY_LIMIT);
+        Self {
+            status,
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            method,
+            url,
+        }
+    }
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ➤  {}", self.method, self.url, self.status)
+    }
+}
+
+impl HttpError {
+    /// Decode the body of the error with the decoder of the API
+    ///
+    /// The error keeps the raw text of the body if there is no decoder or if decoding fails
+    pub(crate) fn decode(self, decoder: Option<&dyn ErrorDecoder>) -> ApiError {
+        match decoder.and_then(|decoder| decoder.decode(&self)) {
+            Some(body) => ApiError::Api(Box::new(ApiErrorResponse { http: self, body })),
+            None => self.into(),
+        }
+    }
+}
+
+/// Failure to deserialize a response of the API
+///
+/// # Attributes
+/// * path - JSON path of the failing field, such as `data[3].user.id`
+/// * type_name - Name of the Rust type the response is deserialized into
+/// * url - URL of the request, with its secrets redacted
+/// * excerpt - Part of the body around the failing field,
+///   truncated to `PARSE_ERROR_EXCERPT_LIMIT` characters
+/// * page - Page of the failing item, for paginated requests
+/// * item - Index of the failing item in its page, for paginated requests
+/// * source - Error of the JSON deserializer
+#[derive(Debug)]
+pub struct ParseError {
+    pub path: String,
+    pub type_name: &'static str,
+    pub url: String,
+    pub excerpt: String,
+    pub page: Option<usize>,
+    pub item: Option<usize>,
+    pub source: serde_json::Error,
+}
+
+impl ParseError {
+    /// Error on the body of a response, the excerpt starts near the failing line and column
+    pub(crate) fn from_body<T>(
+        path: String,
+        source: serde_json::Error,
+        url: String,
+        body: &str,
+    ) -> Self {
+        let offset = body
+            .split_inclusive('\n')
+            .take(source.line().saturating_sub(1))
+            .map(str::len)
+            .sum::<usize>()
+            + source.column().saturating_sub(1);
+        Self {
+            path,
+            type_name: std::any::type_name::<T>(),
+            url,
+            excerpt: excerpt(body, offset),
+            page: None,
+            item: None,
+            source,
+        }
+    }
+
+    /// Set the page and the index of the failing item in its page
+    pub(crate) fn item(mut self, page: usize, item: usize) -> Self {
+        self.page = Some(page);
+        self.item = Some(item);
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at `{}` ({}", self.type_name, self.path, self.url)?;
+        if let Some(page) = self.page {
+            write!(f, ", page {page}")?;
+        }
+        if let Some(item) = self.item {
+            write!(f, ", item {item}")?;
+        }
+        write!(f, "): {} ➤  {}", self.source, self.excerpt)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<ParseError> for ApiError {
+    fn from(error: ParseError) -> Self {
+        ApiError::ResponseParse(Box::new(error))
+    }
+}
+
+/// Up to `PARSE_ERROR_EXCERPT_LIMIT` characters of the body, starting a bit before the byte offset
+fn excerpt(body: &str, offset: usize) -> String {
+    let mut start = offset
+        .saturating_sub(PARSE_ERROR_EXCERPT_LIMIT / 2)
+        .min(body.len());
+    while !body.is_char_boundary(start) {
+        start -= 1;
+    }
+    let excerpt: String = body[start..]
+        .chars()
+        .take(PARSE_ERROR_EXCERPT_LIMIT)
+        .collect();
+    let mut decorated = String::new();
+    if start > 0 {
+        decorated.push('…');
+    }
+    decorated.push_str(&excerpt);
+    if start + excerpt.len() < body.len() {
+        decorated.push('…');
+    }
+    decorated
+}
+
+/// Error response of the API with a decoded body
+///
+/// # Attributes
+/// * http - Response of the API, with the raw text of the body
+/// * body - Body decoded by the error decoder of the API
+#[derive(Debug)]
+pub struct ApiErrorResponse {
+    pub http: HttpError,
+    pub body: Box<dyn ErrorBody>,
+}
+
+impl ApiErrorResponse {
+    /// Decoded body of the error, if it is a `E`
+    pub fn body<E: 'static>(&self) -> Option<&E> {
+        self.body.as_ref().as_any().downcast_ref::<E>()
+    }
+}
+
+impl Display for ApiErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {:?}", self.http, self.body)
+    }
+}
+
+impl From<HttpError> for ApiError {
+    fn from(error: HttpError) -> Self {
+        ApiError::Http(Box::new(error))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 #[error("Connector")]
 pub struct ConnectorError;
@@ -0,0 +1,125 @@
+use std::{any::Any, collections::HashMap, fmt::Debug, fmt::Display, marker::PhantomData};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::error::HttpError;
+
+/// Decoded body of an error response
+///
+/// Implemented for every `Debug + Send + Sync` type,
+/// the concrete type is retrieved with [ApiErrorResponse::body](crate::error::ApiErrorResponse::body)
+pub trait ErrorBody: Debug + Send + Sync + 'static {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Debug + Send + Sync + 'static> ErrorBody for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Decoder of the error responses of an API
+///
+/// When the body is decoded, the error is returned as `ApiError::Api`,
+/// otherwise it is returned as `ApiError::Http` with the raw text of the body.
+pub trait ErrorDecoder: Debug + Send + Sync {
+    /// Decode the body of an error response
+    ///
+    /// Returns `None` to keep the raw text of the body
+    fn decode(&self, error: &HttpError) -> Option<Box<dyn ErrorBody>>;
+}
+
+/// Decode the error responses as JSON into `E`
+#[derive(Debug)]
+pub struct JsonErrorDecoder<E>(PhantomData<fn() -> E>);
+
+impl<E> JsonErrorDecoder<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E> Default for JsonErrorDecoder<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: DeserializeOwned + Debug + Send + Sync + 'static> ErrorDecoder for JsonErrorDecoder<E> {
+    fn decode(&self, error: &HttpError) -> Option<Box<dyn ErrorBody>> {
+        match serde_json::from_str::<E>(&error.body) {
+            Ok(body) => Some(Box::new(body)),
+            Err(e) => {
+                log::info!(
+                    "Error body of {} is not a {}: {e}",
+                    error.url,
+                    std::any::type_name::<E>()
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Problem details of an HTTP API error (RFC 9457, formerly RFC 7807)
+///
+/// # Attributes
+/// * problem_type - URI identifying the problem type (Default = `about:blank`)
+/// * title - Short summary of the problem type
+/// * status - Status code generated by the server
+/// * detail - Explanation specific to this occurrence of the problem
+/// * instance - URI identifying this occurrence of the problem
+/// * extensions - Additional members of the problem
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub problem_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(flatten)]
+    pub extensions: HashMap<String, Value>,
+}
+
+impl Display for ProblemDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.title
+                .as_deref()
+                .or(self.problem_type.as_deref())
+                .unwrap_or("about:blank")
+        )?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {detail}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the `application/problem+json` error responses into [ProblemDetails]
+///
+/// Other responses keep the raw text of their body
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProblemDetailsDecoder;
+
+impl ErrorDecoder for ProblemDetailsDecoder {
+    fn decode(&self, error: &HttpError) -> Option<Box<dyn ErrorBody>> {
+        let is_problem = error
+            .headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/problem+json"));
+        match is_problem {
+            true => JsonErrorDecoder::<ProblemDetails>::new().decode(error),
+            false => None,
+        }
+    }
+}
@@ -1,17 +1,32 @@
 #![doc = include_str!("../README.md")]
 
+pub mod authorization_code;
+pub mod aws_sigv4;
+pub mod circuit_breaker;
+#[cfg(feature = "private-key-jwt")]
+pub mod client_assertion;
 pub mod connector;
 pub mod connector_builder;
+pub mod device_authorization;
+pub mod digest_auth;
 pub mod error;
+pub mod error_decoder;
 pub mod filter;
+pub mod oauth1;
+pub mod oauth2;
+pub mod oidc_discovery;
 pub mod pagination;
 pub mod prelude;
 pub mod query;
 pub mod range;
+pub mod rate_limit_bucket;
+pub mod rate_limit_strategy;
 pub mod rate_limiter;
 pub mod request;
 pub mod request_builder;
+pub mod request_signer;
 pub mod request_url;
+pub mod retry_policy;
 pub mod sort;
 
 #[doc(inline)]
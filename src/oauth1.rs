@@ -0,0 +1,299 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use hmac::{Hmac, Mac};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderValue};
+use rsa::{
+    RsaPrivateKey,
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs1v15::SigningKey,
+    pkcs8::DecodePrivateKey,
+    signature::{SignatureEncoding, Signer},
+};
+use sha1::Sha1;
+
+use crate::{
+    error::{ApiError, Result},
+    request_signer::RequestSigner,
+};
+
+/// Signature method of OAuth 1.0a (RFC 5849 section 3.4)
+///
+/// # Variants
+/// * HmacSha1 - HMAC-SHA1 keyed with the client and token secrets
+/// * RsaSha1 - RSASSA-PKCS1-v1_5 with SHA-1, from the private key of the client
+/// * Plaintext - The client and token secrets, only over TLS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display)]
+pub enum OAuth1SignatureMethod {
+    #[default]
+    #[strum(serialize = "HMAC-SHA1")]
+    HmacSha1,
+    #[strum(serialize = "RSA-SHA1")]
+    RsaSha1,
+    #[strum(serialize = "PLAINTEXT")]
+    Plaintext,
+}
+
+/// Credentials of an OAuth 1.0a client (RFC 5849)
+///
+/// Every attempt is signed with a new nonce and timestamp,
+/// from the final URL of the request and its `application/x-www-form-urlencoded` body.
+///
+/// # Attributes
+/// * consumer_key - Identifier of the client
+/// * consumer_secret - Shared secret of the client, unused with RSA-SHA1
+/// * token - Token of the resource owner
+/// * token_secret - Shared secret of the token
+/// * signature_method - Signature method (Default = HMAC-SHA1)
+/// * private_key - PEM private key of the client, for RSA-SHA1
+/// * realm - Protection realm, sent in the header
+/// * callback - Redirect URI sent while requesting the temporary credentials
+/// * verifier - Verification code sent while requesting the token credentials
+///
+/// # Example
+/// ```rust,ignore
+/// let oauth1 = OAuth1::new("consumer_key", "consumer_secret").token("token", "token_secret");
+/// let api: Api = ApiBuilder::new("https://api.example.com").oauth1(oauth1).build();
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct OAuth1 {
+    pub(crate) consumer_key: String,
+    pub(crate) consumer_secret: String,
+    pub(crate) token: Option<String>,
+    pub(crate) token_secret: String,
+    pub(crate) signature_method: OAuth1SignatureMethod,
+    pub(crate) private_key: Option<String>,
+    pub(crate) realm: Option<String>,
+    pub(crate) callback: Option<String>,
+    pub(crate) verifier: Option<String>,
+}
+
+impl OAuth1 {
+    /// Create the credentials of a client signing with HMAC-SHA1
+    pub fn new(consumer_key: impl ToString, consumer_secret: impl ToString) -> Self {
+        Self {
+            consumer_key: consumer_key.to_string(),
+            consumer_secret: consumer_secret.to_string(),
+            token: None,
+            token_secret: String::new(),
+            signature_method: OAuth1SignatureMethod::HmacSha1,
+            private_key: None,
+            realm: None,
+            callback: None,
+            verifier: None,
+        }
+    }
+
+    /// Create the credentials of a client signing with RSA-SHA1
+    ///
+    /// Returns `ApiError::RequestSigning` if the PEM is not an RSA private key
+    pub fn rsa_sha1(consumer_key: impl ToString, private_key: impl ToString) -> Result<Self> {
+        let private_key = private_key.to_string();
+        Self::rsa_key(&private_key)?;
+        Ok(Self {
+            signature_method: OAuth1SignatureMethod::RsaSha1,
+            private_key: Some(private_key),
+            ..Self::new(consumer_key, "")
+        })
+    }
+
+    pub fn token(mut self, token: impl ToString, token_secret: impl ToString) -> Self {
+        self.token = Some(token.to_string());
+        self.token_secret = token_secret.to_string();
+        self
+    }
+
+    /// Sign with PLAINTEXT, the secrets must only be sent over TLS
+    pub fn plaintext(mut self) -> Self {
+        self.signature_method = OAuth1SignatureMethod::Plaintext;
+        self
+    }
+
+    pub fn realm(mut self, realm: impl ToString) -> Self {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    pub fn callback(mut self, callback: impl ToString) -> Self {
+        self.callback = Some(callback.to_string());
+        self
+    }
+
+    pub fn verifier(mut self, verifier: impl ToString) -> Self {
+        self.verifier = Some(verifier.to_string());
+        self
+    }
+
+    /// Sign the request with the given nonce and timestamp, and set its `Authorization` header
+    pub fn sign_with(
+        &self,
+        request: &mut reqwest::Request,
+        nonce: &str,
+        timestamp: i64,
+    ) -> Result<()> {
+        let mut params = self.protocol_params(nonce, timestamp);
+        let signature = self.signature(&self.signature_base_string(request, nonce, timestamp)?)?;
+        params.push((String::from("oauth_signature"), signature));
+
+        let mut header = String::from("OAuth ");
+        if let Some(realm) = &self.realm {
+            header.push_str(&format!("realm=\"{}\", ", encode(realm)));
+        }
+        header.push_str(
+            &params
+                .iter()
+                .map(|(name, value)| format!("{name}=\"{}\"", encode(value)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, HeaderValue::from_str(&header)?);
+        Ok(())
+    }
+
+    /// Signature base string of the request (RFC 5849 section 3.4.1)
+    ///
+    /// The parameters are the query of the URL, the `application/x-www-form-urlencoded` body
+    /// and the protocol parameters, encoded and sorted.
+    pub fn signature_base_string(
+        &self,
+        request: &reqwest::Request,
+        nonce: &str,
+        timestamp: i64,
+    ) -> Result<String> {
+        let url = request.url();
+        let mut base_uri = url.clone();
+        base_uri.set_query(None);
+        base_uri.set_fragment(None);
+
+        let mut params: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| (encode(&name), encode(&value)))
+            .collect();
+        let is_form = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+        if is_form && let Some(body) = request.body() {
+            let body = body.as_bytes().ok_or_else(|| {
+                ApiError::RequestSigning(String::from("A streamed form body cannot be signed"))
+            })?;
+            params.extend(
+                url::form_urlencoded::parse(body)
+                    .map(|(name, value)| (encode(&name), encode(&value))),
+            );
+        }
+        params.extend(
+            self.protocol_params(nonce, timestamp)
+                .iter()
+                .map(|(name, value)| (encode(name), encode(value))),
+        );
+        params.sort();
+        let params = params
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        Ok(format!(
+            "{}&{}&{}",
+            request.method().as_str().to_uppercase(),
+            encode(base_uri.as_str()),
+            encode(&params)
+        ))
+    }
+
+    /// Protocol parameters, but the signature and the realm
+    fn protocol_params(&self, nonce: &str, timestamp: i64) -> Vec<(String, String)> {
+        let mut params = vec![(
+            String::from("oauth_consumer_key"),
+            self.consumer_key.clone(),
+        )];
+        if let Some(token) = &self.token {
+            params.push((String::from("oauth_token"), token.clone()));
+        }
+        params.push((
+            String::from("oauth_signature_method"),
+            self.signature_method.to_string(),
+        ));
+        params.push((String::from("oauth_timestamp"), timestamp.to_string()));
+        params.push((String::from("oauth_nonce"), nonce.to_string()));
+        if let Some(callback) = &self.callback {
+            params.push((String::from("oauth_callback"), callback.clone()));
+        }
+        if let Some(verifier) = &self.verifier {
+            params.push((String::from("oauth_verifier"), verifier.clone()));
+        }
+        params
+    }
+
+    fn signature(&self, base_string: &str) -> Result<String> {
+        let key = format!(
+            "{}&{}",
+            encode(&self.consumer_secret),
+            encode(&self.token_secret)
+        );
+        match self.signature_method {
+            OAuth1SignatureMethod::HmacSha1 => {
+                let mut mac = Hmac::<Sha1>::new_from_slice(key.as_bytes())
+                    .map_err(|e| ApiError::RequestSigning(e.to_string()))?;
+                mac.update(base_string.as_bytes());
+                Ok(STANDARD.encode(mac.finalize().into_bytes()))
+            }
+            OAuth1SignatureMethod::RsaSha1 => {
+                let private_key = self.private_key.as_deref().ok_or_else(|| {
+                    ApiError::RequestSigning(String::from("RSA-SHA1 requires a private key"))
+                })?;
+                let key = SigningKey::<Sha1>::new(Self::rsa_key(private_key)?);
+                let signature = key
+                    .try_sign(base_string.as_bytes())
+                    .map_err(|e| ApiError::RequestSigning(e.to_string()))?;
+                Ok(STANDARD.encode(signature.to_vec()))
+            }
+            OAuth1SignatureMethod::Plaintext => Ok(key),
+        }
+    }
+
+    /// Read a PKCS#8 or PKCS#1 PEM key
+    fn rsa_key(pem: &str) -> Result<RsaPrivateKey> {
+        RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map_err(|_| ApiError::RequestSigning(String::from("Invalid RSA private key")))
+    }
+}
+
+impl RequestSigner for OAuth1 {
+    /// Sign the request with a new nonce at the current time
+    fn sign(&self, request: &mut reqwest::Request) -> Result<()> {
+        self.sign_with(
+            request,
+            &uuid::Uuid::new_v4().simple().to_string(),
+            chrono::Utc::now().timestamp(),
+        )
+    }
+}
+
+/// The secrets and the private key are never printed
+impl std::fmt::Debug for OAuth1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OAuth1")
+            .field("consumer_key", &self.consumer_key)
+            .field("token", &self.token)
+            .field("signature_method", &self.signature_method)
+            .field("realm", &self.realm)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Percent encode everything but the unreserved characters (RFC 5849 section 3.6)
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,603 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::Instant};
+
+#[cfg(feature = "private-key-jwt")]
+use crate::client_assertion::{ClientAssertionKey, JWT_BEARER_ASSERTION_TYPE};
+use crate::{
+    error::{ApiError, HttpError, Result},
+    error_decoder::JsonErrorDecoder,
+};
+
+/// Successful response of a token endpoint (RFC 6749 section 5.1)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub token_type: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub id_token: Option<String>,
+}
+
+/// Error response of an OAuth2 endpoint (RFC 6749 section 5.2)
+///
+/// Available with `ApiError::api_body::<OAuth2Error>()` on the errors of the token endpoint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OAuth2Error {
+    pub error: String,
+    #[serde(default)]
+    pub error_description: Option<String>,
+    #[serde(default)]
+    pub error_uri: Option<String>,
+}
+
+/// Grant used to get a token from the token endpoint
+///
+/// # Variants
+/// * ClientCredentials - Token of the client itself
+/// * Password - Token of a user, from their username and password
+/// * RefreshToken - New token from a refresh token
+/// * AuthorizationCode - Token of a user, from the code given to the redirect URI
+/// * DeviceCode - Token of a user, once they approved the device code (RFC 8628)
+/// * TokenExchange - Token exchanged for another token, of another audience or subject (RFC 8693)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Grant {
+    ClientCredentials,
+    Password {
+        username: String,
+        password: String,
+    },
+    RefreshToken(String),
+    AuthorizationCode {
+        code: String,
+        redirect_uri: String,
+        code_verifier: Option<String>,
+    },
+    DeviceCode(String),
+    TokenExchange(TokenExchange),
+}
+
+impl Grant {
+    /// Form parameters of the grant
+    fn params(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Grant::ClientCredentials => vec![("grant_type", String::from("client_credentials"))],
+            Grant::Password { username, password } => vec![
+                ("grant_type", String::from("password")),
+                ("username", username.clone()),
+                ("password", password.clone()),
+            ],
+            Grant::RefreshToken(refresh_token) => vec![
+                ("grant_type", String::from("refresh_token")),
+                ("refresh_token", refresh_token.clone()),
+            ],
+            Grant::AuthorizationCode {
+                code,
+                redirect_uri,
+                code_verifier,
+            } => {
+                let mut params = vec![
+                    ("grant_type", String::from("authorization_code")),
+                    ("code", code.clone()),
+                    ("redirect_uri", redirect_uri.clone()),
+                ];
+                if let Some(verifier) = code_verifier {
+                    params.push(("code_verifier", verifier.clone()));
+                }
+                params
+            }
+            Grant::DeviceCode(device_code) => vec![
+                (
+                    "grant_type",
+                    String::from("urn:ietf:params:oauth:grant-type:device_code"),
+                ),
+                ("device_code", device_code.clone()),
+            ],
+            Grant::TokenExchange(exchange) => exchange.params(),
+        }
+    }
+
+    /// Check if the grant can be sent again to get a new token
+    pub fn is_repeatable(&self) -> bool {
+        match self {
+            Grant::ClientCredentials | Grant::Password { .. } | Grant::TokenExchange(_) => true,
+            Grant::RefreshToken(_) | Grant::AuthorizationCode { .. } | Grant::DeviceCode(_) => {
+                false
+            }
+        }
+    }
+}
+
+/// Token type of an access token, the default type of the subject token of a [TokenExchange]
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Token type of a refresh token, to request an offline token with a [TokenExchange]
+pub const REFRESH_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:refresh_token";
+
+/// Token exchange request (RFC 8693 section 2.1)
+///
+/// `requested_subject` and `subject_issuer` are the Keycloak extensions used for impersonation
+/// and for the tokens of another realm or identity provider.
+///
+/// # Attributes
+/// * subject_token - Token to exchange
+/// * subject_token_type - Type of the subject token (Default = access token)
+/// * requested_token_type - Type of the token to issue, the server chooses when None
+/// * audience - Client or service the new token is intended for
+/// * requested_subject - User to impersonate
+/// * subject_issuer - Issuer of the subject token, when it comes from another realm or identity provider
+///
+/// # Example
+/// ```rust,ignore
+/// let exchange = TokenExchange::new(user_token).audience("billing-service");
+/// let token = client.request_token(&Grant::TokenExchange(exchange)).await?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenExchange {
+    pub(crate) subject_token: String,
+    pub(crate) subject_token_type: String,
+    pub(crate) requested_token_type: Option<String>,
+    pub(crate) audience: Option<String>,
+    pub(crate) requested_subject: Option<String>,
+    pub(crate) subject_issuer: Option<String>,
+}
+
+impl TokenExchange {
+    pub fn new(subject_token: impl ToString) -> Self {
+        Self {
+            subject_token: subject_token.to_string(),
+            subject_token_type: String::from(ACCESS_TOKEN_TYPE),
+            requested_token_type: None,
+            audience: None,
+            requested_subject: None,
+            subject_issuer: None,
+        }
+    }
+
+    pub fn subject_token_type(mut self, token_type: impl ToString) -> Self {
+        self.subject_token_type = token_type.to_string();
+        self
+    }
+
+    pub fn requested_token_type(mut self, token_type: impl ToString) -> Self {
+        self.requested_token_type = Some(token_type.to_string());
+        self
+    }
+
+    pub fn audience(mut self, audience: impl ToString) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    pub fn requested_subject(mut self, subject: impl ToString) -> Self {
+        self.requested_subject = Some(subject.to_string());
+        self
+    }
+
+    pub fn subject_issuer(mut self, issuer: impl ToString) -> Self {
+        self.subject_issuer = Some(issuer.to_string());
+        self
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            (
+                "grant_type",
+                String::from("urn:ietf:params:oauth:grant-type:token-exchange"),
+            ),
+            ("subject_token", self.subject_token.clone()),
+            ("subject_token_type", self.subject_token_type.clone()),
+        ];
+        let optional = [
+            ("requested_token_type", &self.requested_token_type),
+            ("audience", &self.audience),
+            ("requested_subject", &self.requested_subject),
+            ("subject_issuer", &self.subject_issuer),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                params.push((name, value.clone()));
+            }
+        }
+        params
+    }
+}
+
+/// Method used by the client to authenticate to the token endpoint
+///
+/// Public clients, without a secret, only send their identifier in the form
+///
+/// # Variants
+/// * ClientSecretPost - Identifier and secret in the form
+/// * ClientSecretBasic - Identifier and secret in a Basic authorization header
+/// * PrivateKeyJwt - JWT assertion signed with the private key of the client (RFC 7523), with the `private-key-jwt` feature
+/// * TlsClientAuth - Certificate of the client in the TLS handshake (RFC 8705)
+/// * None - Identifier in the form, for public clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ClientAuthMethod {
+    #[default]
+    ClientSecretPost,
+    ClientSecretBasic,
+    PrivateKeyJwt,
+    TlsClientAuth,
+    None,
+}
+
+/// Client of an OAuth2 token endpoint
+///
+/// # Attributes
+/// * token_endpoint - URL of the token endpoint
+/// * client_id - Identifier of the client
+/// * client_secret - Secret of a confidential client, None for a public client
+/// * scopes - Scopes requested with the token
+/// * auth_method - Method used to authenticate to the token endpoint (Default = client_secret_post)
+/// * assertion_key - Key signing the client assertions of the private_key_jwt auth method
+/// * audience - API the tokens are intended for, required by Auth0
+/// * resources - Resource indicators of the APIs the tokens are intended for (RFC 8707), as Azure AD `resource`
+/// * http - HTTP client used to call the token endpoint
+///
+/// # Example
+/// ```rust,ignore
+/// let client = OAuth2Client::new("https://auth.example.com/token", "client_id")
+///     .client_secret("client_secret")
+///     .scopes(["read", "write"]);
+/// let token = client.request_token(&Grant::ClientCredentials).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct OAuth2Client {
+    pub(crate) token_endpoint: String,
+    pub(crate) client_id: String,
+    pub(crate) client_secret: Option<String>,
+    pub(crate) scopes: Vec<String>,
+    pub(crate) auth_method: ClientAuthMethod,
+    #[cfg(feature = "private-key-jwt")]
+    pub(crate) assertion_key: Option<ClientAssertionKey>,
+    pub(crate) audience: Option<String>,
+    pub(crate) resources: Vec<String>,
+    pub(crate) http: Client,
+}
+
+impl OAuth2Client {
+    pub fn new(token_endpoint: impl ToString, client_id: impl ToString) -> Self {
+        Self {
+            token_endpoint: token_endpoint.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: None,
+            scopes: Vec::new(),
+            auth_method: ClientAuthMethod::default(),
+            #[cfg(feature = "private-key-jwt")]
+            assertion_key: None,
+            audience: None,
+            resources: Vec::new(),
+            http: Client::new(),
+        }
+    }
+
+    pub fn client_secret(mut self, secret: impl ToString) -> Self {
+        self.client_secret = Some(secret.to_string());
+        self
+    }
+
+    pub fn scopes<T: IntoIterator>(mut self, scopes: T) -> Self
+    where
+        T::Item: ToString,
+    {
+        self.scopes = scopes.into_iter().map(|scope| scope.to_string()).collect();
+        self
+    }
+
+    pub fn auth_method(mut self, method: ClientAuthMethod) -> Self {
+        self.auth_method = method;
+        self
+    }
+
+    /// Authenticate with JWT assertions signed by the key
+    #[cfg(feature = "private-key-jwt")]
+    pub fn private_key_jwt(mut self, key: ClientAssertionKey) -> Self {
+        self.auth_method = ClientAuthMethod::PrivateKeyJwt;
+        self.assertion_key = Some(key);
+        self
+    }
+
+    /// Authenticate with a client certificate, from the PEM certificate chain and PKCS#8 private key
+    ///
+    /// Replaces the HTTP client, use `http_client` with an identity to keep other settings
+    pub fn tls_client_auth(
+        mut self,
+        certificate_pem: impl AsRef<[u8]>,
+        key_pem: impl AsRef<[u8]>,
+    ) -> Result<Self> {
+        let identity =
+            reqwest::Identity::from_pkcs8_pem(certificate_pem.as_ref(), key_pem.as_ref())
+                .map_err(ApiError::ReqwestBuilder)?;
+        self.http = Client::builder()
+            .identity(identity)
+            .build()
+            .map_err(ApiError::ReqwestBuilder)?;
+        self.auth_method = ClientAuthMethod::TlsClientAuth;
+        Ok(self)
+    }
+
+    pub fn audience(mut self, audience: impl ToString) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    /// Add a resource indicator, sent as `resource`
+    pub fn resource(mut self, resource: impl ToString) -> Self {
+        self.resources.push(resource.to_string());
+        self
+    }
+
+    /// Set the HTTP client used to call the token endpoint
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http = client;
+        self
+    }
+
+    pub fn token_endpoint(&self) -> &str {
+        &self.token_endpoint
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// HTTP client used to call the token endpoint, with the TLS identity of the client if any
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    /// Request a token from the token endpoint
+    ///
+    /// Error responses are returned as `ApiError::Api` with an [OAuth2Error] body when possible
+    pub async fn request_token(&self, grant: &Grant) -> Result<TokenResponse> {
+        let mut params = grant.params();
+        if !self.scopes.is_empty()
+            && !matches!(
+                grant,
+                Grant::RefreshToken(_) | Grant::AuthorizationCode { .. } | Grant::DeviceCode(_)
+            )
+        {
+            params.push(("scope", self.scopes.join(" ")));
+        }
+        let mut audience = self.audience_params();
+        // The audience of a token exchange is part of the grant
+        if matches!(grant, Grant::TokenExchange(_)) {
+            audience.retain(|(name, _)| *name != "audience");
+        }
+        params.extend(audience);
+        self.post_form(&self.token_endpoint, params).await
+    }
+
+    /// Audience and resource parameters, also sent to the authorization and device authorization endpoints
+    pub(crate) fn audience_params(&self) -> Vec<(&'static str, String)> {
+        let audience = self.audience.iter().map(|a| ("audience", a.clone()));
+        let resources = self.resources.iter().map(|r| ("resource", r.clone()));
+        audience.chain(resources).collect()
+    }
+
+    /// Post a form to an endpoint of the authorization server and parse the JSON response
+    ///
+    /// The client authenticates with its auth method
+    pub(crate) async fn post_form<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        mut params: Vec<(&str, String)>,
+    ) -> Result<T> {
+        let mut request = self
+            .http
+            .post(endpoint)
+            .header(reqwest::header::ACCEPT, "application/json");
+        match (self.auth_method, &self.client_secret) {
+            (ClientAuthMethod::ClientSecretPost, Some(secret)) => {
+                params.push(("client_id", self.client_id.clone()));
+                params.push(("client_secret", secret.clone()));
+            }
+            (ClientAuthMethod::ClientSecretBasic, Some(secret)) => {
+                // The identifier and the secret are form encoded before being Base64 encoded (RFC 6749 section 2.3.1)
+                request =
+                    request.basic_auth(form_encode(&self.client_id), Some(form_encode(secret)));
+            }
+            #[cfg(feature = "private-key-jwt")]
+            (ClientAuthMethod::PrivateKeyJwt, _) => {
+                let key = self.assertion_key.as_ref().ok_or_else(|| {
+                    ApiError::OAuth2Flow(String::from(
+                        "private_key_jwt needs a client assertion key",
+                    ))
+                })?;
+                params.push(("client_id", self.client_id.clone()));
+                params.push((
+                    "client_assertion_type",
+                    String::from(JWT_BEARER_ASSERTION_TYPE),
+                ));
+                params.push((
+                    "client_assertion",
+                    key.assertion(&self.client_id, &self.token_endpoint)?,
+                ));
+            }
+            #[cfg(not(feature = "private-key-jwt"))]
+            (ClientAuthMethod::PrivateKeyJwt, _) => {
+                return Err(ApiError::OAuth2Flow(String::from(
+                    "private_key_jwt needs the private-key-jwt feature",
+                )));
+            }
+            _ => {
+                params.push(("client_id", self.client_id.clone()));
+            }
+        }
+        let request = request
+            .form(&params)
+            .build()
+            .map_err(ApiError::ReqwestBuilder)?;
+        let method = request.method().clone();
+        let response = self
+            .http
+            .execute(request)
+            .await
+            .map_err(ApiError::ReqwestExecute)?;
+        if !response.status().is_success() {
+            let error = HttpError::from_response(method, response).await;
+            return Err(error.decode(Some(&JsonErrorDecoder::<OAuth2Error>::new())));
+        }
+        let text = response.text().await.map_err(ApiError::ResponseToText)?;
+        serde_json::from_str(&text).map_err(ApiError::TokenResponseParse)
+    }
+}
+
+fn form_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Token held by a [TokenSource]
+#[derive(Debug, Clone)]
+struct Token {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Option<Instant>,
+    lifetime: Option<Duration>,
+}
+
+impl Token {
+    fn new(response: TokenResponse, previous_refresh_token: Option<String>) -> Self {
+        let lifetime = response.expires_in.map(Duration::from_secs);
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token.or(previous_refresh_token),
+            expires_at: lifetime.map(|lifetime| Instant::now() + lifetime),
+            lifetime,
+        }
+    }
+
+    /// Check if the token can still be used `margin` from now
+    ///
+    /// The margin is capped to half the lifetime of the token
+    fn is_fresh(&self, margin: Duration) -> bool {
+        let Some(expires_at) = self.expires_at else {
+            return true;
+        };
+        let margin = self
+            .lifetime
+            .map_or(margin, |lifetime| margin.min(lifetime / 2));
+        Instant::now() + margin < expires_at
+    }
+}
+
+/// Source of the access tokens of an API
+///
+/// The token is renewed before it expires, with the refresh token when the server gave one,
+/// or by sending the grant again.\
+/// Requests of the API get their token from the source before every attempt,
+/// and are sent once more with a new token after a 401 response.\
+/// The source is locked while renewing, so concurrent requests wait for a single renewal.
+///
+/// # Attributes
+/// * client - Client of the token endpoint
+/// * grant - Grant used to get the first token
+/// * refresh_margin - Time before expiry when the token is renewed (Default = 30s)
+#[derive(Debug)]
+pub struct TokenSource {
+    client: OAuth2Client,
+    grant: Grant,
+    refresh_margin: Duration,
+    token: Mutex<Option<Token>>,
+}
+
+impl TokenSource {
+    /// Create a source getting its first token with the grant
+    pub fn new(client: OAuth2Client, grant: Grant) -> Self {
+        Self {
+            client,
+            grant,
+            refresh_margin: Duration::from_secs(30),
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Create a source from a token already obtained with the grant
+    pub fn with_token(client: OAuth2Client, grant: Grant, response: TokenResponse) -> Self {
+        Self {
+            token: Mutex::new(Some(Token::new(response, None))),
+            ..Self::new(client, grant)
+        }
+    }
+
+    /// Set the time before expiry when the token is renewed
+    pub fn refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    pub fn client(&self) -> &OAuth2Client {
+        &self.client
+    }
+
+    /// Current access token, renewed if it is about to expire
+    pub async fn token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(current) = token.as_ref().filter(|t| t.is_fresh(self.refresh_margin)) {
+            return Ok(current.access_token.clone());
+        }
+        // The refresh token of a refresh token grant is kept until the server rotates it
+        let previous_refresh_token = token
+            .as_ref()
+            .and_then(|t| t.refresh_token.clone())
+            .or_else(|| match &self.grant {
+                Grant::RefreshToken(refresh_token) => Some(refresh_token.clone()),
+                _ => None,
+            });
+        let response = self.renew(previous_refresh_token.clone()).await?;
+        let renewed = token.insert(Token::new(response, previous_refresh_token));
+        Ok(renewed.access_token.clone())
+    }
+
+    /// Current refresh token, if the server gave one
+    pub async fn refresh_token(&self) -> Option<String> {
+        self.token
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|t| t.refresh_token.clone())
+    }
+
+    /// Mark the token as rejected by the server, so the next call to `token` renews it
+    ///
+    /// Nothing is done if the token was already renewed by another request
+    pub async fn invalidate(&self, access_token: &str) {
+        if let Some(token) = self.token.lock().await.as_mut()
+            && token.access_token == access_token
+        {
+            token.expires_at = Some(Instant::now());
+        }
+    }
+
+    /// Get a new token with the refresh token, or with the grant
+    async fn renew(&self, refresh_token: Option<String>) -> Result<TokenResponse> {
+        let Some(refresh_token) = refresh_token else {
+            log::info!("Requesting a token from {}", self.client.token_endpoint);
+            return self.client.request_token(&self.grant).await;
+        };
+        log::info!("Refreshing the token from {}", self.client.token_endpoint);
+        match self
+            .client
+            .request_token(&Grant::RefreshToken(refresh_token))
+            .await
+        {
+            Ok(response) => Ok(response),
+            Err(e) if self.grant.is_repeatable() => {
+                log::error!("Token refresh error: {e}, requesting a new token");
+                self.client.request_token(&self.grant).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
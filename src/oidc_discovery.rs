@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{
+    authorization_code::AuthorizationCodeFlow,
+    device_authorization::DeviceCodeFlow,
+    error::{ApiError, HttpError, Result},
+    oauth2::{ClientAuthMethod, OAuth2Client},
+};
+
+/// Default time the discovered provider metadata are kept in the cache
+pub const DISCOVERY_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Provider metadata by issuer, with the instant they were fetched
+///
+/// # Attributes
+/// * ttl - Time the metadata are kept (Default = [DISCOVERY_CACHE_TTL])
+/// * entries - Metadata by issuer, with the instant they were fetched
+struct DiscoveryCache {
+    ttl: Duration,
+    entries: HashMap<String, (Instant, Arc<ProviderMetadata>)>,
+}
+
+/// Provider metadata discovered by issuer, shared by every connector of the process
+static DISCOVERY_CACHE: LazyLock<Mutex<DiscoveryCache>> = LazyLock::new(|| {
+    Mutex::new(DiscoveryCache {
+        ttl: DISCOVERY_CACHE_TTL,
+        entries: HashMap::new(),
+    })
+});
+
+/// Metadata of an OpenID provider (OpenID Connect Discovery 1.0 section 3)
+///
+/// Only the members used by reqt are parsed.\
+/// `grant_types_supported` and `token_endpoint_auth_methods_supported` are None when the provider does not list them.
+///
+/// # Example
+/// ```rust,ignore
+/// let provider = ProviderMetadata::discover(&Client::new(), "https://auth.example.com/realms/main").await?;
+/// let client = provider.client("client_id")?.client_secret("client_secret");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    #[serde(default)]
+    pub authorization_endpoint: Option<String>,
+    #[serde(default)]
+    pub token_endpoint: Option<String>,
+    #[serde(default)]
+    pub device_authorization_endpoint: Option<String>,
+    #[serde(default)]
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub jwks_uri: Option<String>,
+    #[serde(default)]
+    pub scopes_supported: Option<Vec<String>>,
+    #[serde(default)]
+    pub grant_types_supported: Option<Vec<String>>,
+    #[serde(default)]
+    pub token_endpoint_auth_methods_supported: Option<Vec<String>>,
+}
+
+impl ProviderMetadata {
+    /// URL of the discovery document of an issuer
+    pub fn discovery_url(issuer: &str) -> String {
+        format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        )
+    }
+
+    /// Discover the metadata of an issuer with the HTTP client, from the cache if they are recent enough
+    ///
+    /// Use the client of the token endpoint, [OAuth2Client::http], to keep its proxy and TLS identity
+    pub async fn discover(client: &Client, issuer: &str) -> Result<Arc<Self>> {
+        let key = issuer.trim_end_matches('/').to_string();
+        {
+            let cache = Self::cache();
+            if let Some((fetched_at, metadata)) = cache.entries.get(&key)
+                && fetched_at.elapsed() < cache.ttl
+            {
+                return Ok(metadata.clone());
+            }
+        }
+        let metadata = Arc::new(Self::fetch(client, issuer).await?);
+        Self::cache()
+            .entries
+            .insert(key, (Instant::now(), metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// Discover the metadata of an issuer, or log the error and return None
+    pub async fn try_discover(client: &Client, issuer: &str) -> Option<Arc<Self>> {
+        match Self::discover(client, issuer).await {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                log::warn!("Discovery of the OpenID provider {issuer} failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// Set the time the discovered metadata are kept in the cache, `Duration::ZERO` disables it
+    pub fn set_cache_ttl(ttl: Duration) {
+        Self::cache().ttl = ttl;
+    }
+
+    /// Remove the metadata of an issuer from the cache, to discover them again
+    pub fn invalidate(issuer: &str) {
+        Self::cache().entries.remove(issuer.trim_end_matches('/'));
+    }
+
+    /// Remove every discovered metadata from the cache
+    pub fn clear_cache() {
+        Self::cache().entries.clear();
+    }
+
+    /// Fetch the discovery document of an issuer, without the cache
+    ///
+    /// Returns `ApiError::DiscoveryParse` if the document is malformed,
+    /// and `ApiError::OAuth2Flow` if it belongs to another issuer
+    pub async fn fetch(client: &Client, issuer: &str) -> Result<Self> {
+        let url = Self::discovery_url(issuer);
+        log::info!("Discovering the OpenID provider {issuer}");
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .map_err(ApiError::ReqwestExecute)?;
+        if !response.status().is_success() {
+            return Err(ApiError::Http(Box::new(
+                HttpError::from_response(reqwest::Method::GET, response).await,
+            )));
+        }
+        let text = response.text().await.map_err(ApiError::ResponseToText)?;
+        let metadata: Self = serde_json::from_str(&text).map_err(ApiError::DiscoveryParse)?;
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(ApiError::OAuth2Flow(format!(
+                "Discovery document of {issuer} belongs to {}",
+                metadata.issuer
+            )));
+        }
+        Ok(metadata)
+    }
+
+    /// Check if the provider supports a grant type
+    ///
+    /// Grants are assumed supported when the provider does not list them
+    pub fn supports_grant(&self, grant_type: &str) -> bool {
+        self.grant_types_supported
+            .as_ref()
+            .is_none_or(|grants| grants.iter().any(|grant| grant == grant_type))
+    }
+
+    /// Check if the token endpoint supports a client auth method
+    ///
+    /// Without a list, only `client_secret_basic` is supported (RFC 8414 section 2)
+    pub fn supports_auth_method(&self, method: &str) -> bool {
+        match &self.token_endpoint_auth_methods_supported {
+            Some(methods) => methods.iter().any(|m| m == method),
+            None => method == "client_secret_basic",
+        }
+    }
+
+    /// Return an error if the provider does not support the grant type
+    pub fn require_grant(&self, grant_type: &str) -> Result<()> {
+        match self.supports_grant(grant_type) {
+            true => Ok(()),
+            false => Err(ApiError::OAuth2Flow(format!(
+                "{} does not support the {grant_type} grant",
+                self.issuer
+            ))),
+        }
+    }
+
+    /// Client of the token endpoint, authenticating with the first supported secret method
+    pub fn client(&self, client_id: impl ToString) -> Result<OAuth2Client> {
+        Ok(self
+            .configure(OAuth2Client::new(String::new(), client_id))?
+            .auth_method(self.secret_auth_method()))
+    }
+
+    /// Point a client at the token endpoint of the provider, keeping its credentials and HTTP client
+    pub fn configure(&self, mut client: OAuth2Client) -> Result<OAuth2Client> {
+        client.token_endpoint = self.endpoint(&self.token_endpoint, "token")?.to_string();
+        Ok(client)
+    }
+
+    /// First secret auth method supported by the token endpoint, `client_secret_basic` or `client_secret_post`
+    pub fn secret_auth_method(&self) -> ClientAuthMethod {
+        [
+            ClientAuthMethod::ClientSecretBasic,
+            ClientAuthMethod::ClientSecretPost,
+        ]
+        .into_iter()
+        .find(|method| self.supports_auth_method(&method.to_string()))
+        .unwrap_or_default()
+    }
+
+    /// Authorization code flow using the authorization endpoint of the provider
+    pub fn authorization_code_flow(&self, client: OAuth2Client) -> Result<AuthorizationCodeFlow> {
+        self.require_grant("authorization_code")?;
+        let endpoint = self.endpoint(&self.authorization_endpoint, "authorization")?;
+        Ok(AuthorizationCodeFlow::new(client, endpoint))
+    }
+
+    /// Device authorization grant using the device authorization endpoint of the provider
+    pub fn device_code_flow(&self, client: OAuth2Client) -> Result<DeviceCodeFlow> {
+        self.require_grant("urn:ietf:params:oauth:grant-type:device_code")?;
+        let endpoint =
+            self.endpoint(&self.device_authorization_endpoint, "device authorization")?;
+        Ok(DeviceCodeFlow::new(client, endpoint))
+    }
+
+    fn endpoint<'a>(&self, endpoint: &'a Option<String>, name: &str) -> Result<&'a str> {
+        endpoint
+            .as_deref()
+            .ok_or_else(|| ApiError::OAuth2Flow(format!("{} has no {name} endpoint", self.issuer)))
+    }
+
+    fn cache() -> MutexGuard<'static, DiscoveryCache> {
+        DISCOVERY_CACHE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
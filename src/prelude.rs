@@ -1,13 +1,37 @@
-pub use crate::connector::{Api, Authorization, AuthorizationType, Connector};
+pub use crate::authorization_code::{AuthorizationCodeFlow, Pkce};
+pub use crate::aws_sigv4::{AwsCredentials, AwsSigV4};
+pub use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+#[cfg(feature = "private-key-jwt")]
+pub use crate::client_assertion::{ClientAssertionKey, SigningAlgorithm};
+pub use crate::connector::{Api, ApiKeyLocation, Authorization, AuthorizationType, Connector};
 pub use crate::connector_builder::ApiBuilder;
-pub use crate::error::{ApiError, ConnectorError, ErrorContext, Result};
+pub use crate::device_authorization::{DeviceAuthorization, DeviceCodeFlow};
+pub use crate::digest_auth::{DigestAlgorithm, DigestAuth, DigestChallenge, DigestQop};
+pub use crate::error::{
+    ApiError, ApiErrorResponse, ConnectorError, ErrorContext, HTTP_ERROR_BODY_LIMIT, HttpError,
+    ParseError, Result,
+};
+pub use crate::error_decoder::{
+    ErrorDecoder, JsonErrorDecoder, ProblemDetails, ProblemDetailsDecoder,
+};
 pub use crate::filter::{Filter, FilterRule};
+pub use crate::oauth1::{OAuth1, OAuth1SignatureMethod};
+pub use crate::oauth2::{
+    ACCESS_TOKEN_TYPE, ClientAuthMethod, Grant, OAuth2Client, OAuth2Error, REFRESH_TOKEN_TYPE,
+    TokenExchange, TokenResponse, TokenSource,
+};
+pub use crate::oidc_discovery::ProviderMetadata;
 pub use crate::pagination::{Pagination, PaginationRule, RequestPagination};
-pub use crate::query::Query;
+pub use crate::query::{Query, TryIntoQuery};
 pub use crate::range::{Range, RangeRule};
 pub use crate::request::Request;
 pub use crate::request_builder::RequestBuilder;
+pub use crate::request_signer::{
+    HmacAlgorithm, HmacSigner, Placement, RequestSigner, SignatureEncoding, SignedPart,
+    TimestampFormat,
+};
 pub use crate::request_url::RequestUrl;
+pub use crate::retry_policy::{RetryErrorKind, RetryPolicy};
 pub use crate::sort::{Sort, SortOrder, SortRule};
 pub use authorization_derive::{ApiKey, Authorization, Basic, Bearer, Oauth2};
 pub use filter_derive::Filter;
@@ -48,28 +48,54 @@ impl Debug for Query {
     }
 }
 
+/// Parse a query string such as `key=value&other=value`
+///
+/// Never fails, the parameters are percent-encoded in the URL of the request.\
+/// Use [TryIntoQuery] to reject a parameter without a key or containing a whitespace or a `#`
 impl FromStr for Query {
     type Err = ApiError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.is_empty() {
-            return Ok(Query::new());
-        }
-
-        let split_params: Vec<String> = s.split('&').map(|s| s.to_owned()).collect();
-        Ok(Query(split_params))
+        Ok(parse(s))
     }
 }
 
+/// Split a query string into its parameters, ignoring the empty ones
+fn params(s: &str) -> impl Iterator<Item = &str> {
+    s.strip_prefix('?')
+        .unwrap_or(s)
+        .split('&')
+        .filter(|param| !param.is_empty())
+}
+
+fn parse(s: &str) -> Query {
+    Query(params(s).map(String::from).collect())
+}
+
+/// Parse a query string, returning `InvalidQuery` for a parameter without a key
+/// or containing a whitespace or a `#`
+fn parse_strict(s: &str) -> Result<Query, ApiError> {
+    params(s)
+        .map(|param| {
+            let key = param.split('=').next().unwrap_or_default();
+            match key.is_empty() || param.contains(|c: char| c.is_whitespace() || c == '#') {
+                true => Err(ApiError::InvalidQuery(param.to_string())),
+                false => Ok(param.to_string()),
+            }
+        })
+        .collect::<Result<_, _>>()
+        .map(Query)
+}
+
 impl From<&str> for Query {
     fn from(s: &str) -> Self {
-        Query::from_str(s).unwrap()
+        parse(s)
     }
 }
 
 impl From<String> for Query {
     fn from(s: String) -> Self {
-        Query::from_str(&s).unwrap()
+        parse(&s)
     }
 }
 
@@ -81,21 +107,17 @@ impl<T: ToString, U: ToString> From<(T, U)> for Query {
 
 impl From<&[&str]> for Query {
     fn from(params: &[&str]) -> Self {
-        let mut query = Query::new();
-        for param in params {
-            query = query.join(Query::from_str(param).unwrap());
-        }
-        query
+        params
+            .iter()
+            .fold(Query::new(), |query, param| query.join(parse(param)))
     }
 }
 
 impl From<&[String]> for Query {
     fn from(params: &[String]) -> Self {
-        let mut query = Query::new();
-        for param in params {
-            query = query.join(Query::from_str(param).unwrap());
-        }
-        query
+        params
+            .iter()
+            .fold(Query::new(), |query, param| query.join(parse(param)))
     }
 }
 
@@ -108,3 +130,57 @@ impl<T: ToString, U: ToString> From<&[(T, U)]> for Query {
         query
     }
 }
+
+/// Conversion into a query, returning `ApiError::InvalidQuery` for a malformed parameter
+///
+/// The fallible alternative of the `From` implementations of [Query]:
+/// `TryFrom` cannot be implemented for the types already converted with `From`.
+pub trait TryIntoQuery {
+    fn try_into_query(self) -> Result<Query, ApiError>;
+}
+
+impl TryIntoQuery for Query {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        Ok(self)
+    }
+}
+
+impl TryIntoQuery for &str {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        parse_strict(self)
+    }
+}
+
+impl TryIntoQuery for String {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        parse_strict(&self)
+    }
+}
+
+impl<T: ToString, U: ToString> TryIntoQuery for (T, U) {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        Ok(self.into())
+    }
+}
+
+impl TryIntoQuery for &[&str] {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        self.iter().try_fold(Query::new(), |query, param| {
+            Ok(query.join(parse_strict(param)?))
+        })
+    }
+}
+
+impl TryIntoQuery for &[String] {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        self.iter().try_fold(Query::new(), |query, param| {
+            Ok(query.join(parse_strict(param)?))
+        })
+    }
+}
+
+impl<T: ToString, U: ToString> TryIntoQuery for &[(T, U)] {
+    fn try_into_query(self) -> Result<Query, ApiError> {
+        Ok(self.into())
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+use tokio::sync::Mutex;
+
+use crate::rate_limiter::{RateLimitHeaders, RateLimiter};
+
+/// Rate limiter applied to the routes matching a pattern
+///
+/// # Attributes
+/// * name - Name of the quota, as reported by the server (`X-RateLimit-Resource` or `RateLimit-Policy`)
+/// * pattern - Route prefix, where `*` matches any single path segment
+/// * limiter - Rate limiter shared by the matching requests
+///
+/// # Example
+/// ```rust,ignore
+/// // Matches `/search/issues` and `/search/code?q=reqt`
+/// RateLimitBucket::new("search", "/search", RateLimiter::new(30, TimePeriod::Minute));
+/// // Matches `/repos/reqt/issues` and `/repos/reqt/issues/42`
+/// RateLimitBucket::new("issues", "/repos/*/issues", RateLimiter::new(10, TimePeriod::Second));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimitBucket {
+    pub(crate) name: String,
+    pub(crate) pattern: String,
+    pub(crate) limiter: Arc<Mutex<RateLimiter>>,
+}
+
+impl RateLimitBucket {
+    pub fn new(name: impl ToString, pattern: impl ToString, limiter: RateLimiter) -> Self {
+        Self {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            limiter: Arc::new(Mutex::new(limiter)),
+        }
+    }
+
+    /// Getter for the name of the bucket
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Getter for the rate limiter of the bucket
+    pub fn limiter(&self) -> Arc<Mutex<RateLimiter>> {
+        self.limiter.clone()
+    }
+
+    /// Check if the route starts with the pattern, segment by segment
+    pub fn matches(&self, route: &str) -> bool {
+        let route = route.split(['?', '#']).next().unwrap_or_default();
+        let mut route_segments = route.split('/').filter(|segment| !segment.is_empty());
+        self.pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .all(|pattern| {
+                route_segments
+                    .next()
+                    .is_some_and(|segment| pattern == "*" || pattern == segment)
+            })
+    }
+}
+
+/// Rate limit buckets of an API, with a fallback for the unmatched routes
+///
+/// Buckets are checked in the order they were registered, the first match wins.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitBuckets {
+    pub(crate) buckets: Arc<Vec<RateLimitBucket>>,
+    pub(crate) fallback: Arc<Mutex<RateLimiter>>,
+}
+
+impl RateLimitBuckets {
+    pub fn new(buckets: Vec<RateLimitBucket>, fallback: Arc<Mutex<RateLimiter>>) -> Self {
+        Self {
+            buckets: Arc::new(buckets),
+            fallback,
+        }
+    }
+
+    /// Rate limiter to be used for a route
+    pub fn limiter_for(&self, route: &str) -> Arc<Mutex<RateLimiter>> {
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.matches(route))
+            .map_or_else(|| self.fallback.clone(), RateLimitBucket::limiter)
+    }
+
+    /// Rate limiter of the bucket with the given name
+    pub fn named(&self, name: &str) -> Option<Arc<Mutex<RateLimiter>>> {
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.name.eq_ignore_ascii_case(name))
+            .map(RateLimitBucket::limiter)
+    }
+
+    /// Update the rate limiter named by the server in the headers,
+    /// or the given one if the server does not name a known bucket
+    pub(crate) async fn update(&self, limiter: &Mutex<RateLimiter>, headers: &HeaderMap) {
+        match RateLimitHeaders::resource(headers).and_then(|name| self.named(&name)) {
+            Some(named) => named.lock().await.update(headers),
+            None => limiter.lock().await.update(headers),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::{collections::VecDeque, fmt::Debug, time::Duration};
+
+use tokio::time::Instant;
+
+/// Algorithm used by the [RateLimiter](crate::rate_limiter::RateLimiter)
+/// to decide when a request can be sent
+///
+/// The limit and the period are owned by the rate limiter and given on each call,
+/// so the strategy follows them when they are updated by the server headers.\
+/// A `limit` of 0 is handled as 1 by the strategies of the crate.
+///
+/// # Implementations
+/// * [FixedWindow] - `limit` requests per window, reset at the end of the window (Default)
+/// * [SlidingLog] - `limit` requests during any `period`
+/// * [TokenBucket] - Bucket of `limit` tokens, refilled continuously
+/// * [Gcra] - Generic Cell Rate Algorithm, requests are evenly spaced with a burst of `limit`
+pub trait RateLimitStrategy: Debug + Send + Sync {
+    /// Consume one request if it is allowed at `now`
+    ///
+    /// Returns the time to wait before a request may be allowed otherwise
+    fn try_acquire(&mut self, limit: u32, period: Duration, now: Instant) -> Option<Duration>;
+
+    /// Time to wait before a request is allowed, without consuming it
+    ///
+    /// Returns `None` if a request is allowed at `now`
+    fn wait_time(&self, limit: u32, period: Duration, now: Instant) -> Option<Duration>;
+
+    /// Number of requests that can be sent at `now` without waiting
+    fn remaining(&self, limit: u32, period: Duration, now: Instant) -> u32;
+
+    /// Synchronize the state with the budget reported by the server
+    ///
+    /// `remaining` requests can be sent until the quota resets in `reset_after`
+    fn sync(
+        &mut self,
+        limit: u32,
+        period: Duration,
+        remaining: u32,
+        reset_after: Duration,
+        now: Instant,
+    );
+
+    /// Clone the strategy into a new box
+    fn box_clone(&self) -> Box<dyn RateLimitStrategy>;
+}
+
+impl Clone for Box<dyn RateLimitStrategy> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/// Fixed window strategy
+///
+/// The window starts with the first request and allows `limit` requests until it ends.\
+/// Bursts of `2 * limit` requests are possible around the end of a window.
+#[derive(Debug, Clone, Default)]
+pub struct FixedWindow {
+    reset_at: Option<Instant>,
+    count: u32,
+}
+
+impl RateLimitStrategy for FixedWindow {
+    fn try_acquire(&mut self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        let reset_at = match self.reset_at {
+            Some(reset_at) if now < reset_at => reset_at,
+            _ => {
+                self.count = 0;
+                *self.reset_at.insert(now + period)
+            }
+        };
+        if self.count < limit {
+            self.count += 1;
+            return None;
+        }
+        Some(reset_at.duration_since(now))
+    }
+
+    fn wait_time(&self, limit: u32, _period: Duration, now: Instant) -> Option<Duration> {
+        match self.reset_at {
+            Some(reset_at) if now < reset_at && self.count >= limit => {
+                Some(reset_at.duration_since(now))
+            }
+            _ => None,
+        }
+    }
+
+    fn remaining(&self, limit: u32, _period: Duration, now: Instant) -> u32 {
+        match self.reset_at {
+            Some(reset_at) if now < reset_at => limit.saturating_sub(self.count),
+            _ => limit,
+        }
+    }
+
+    fn sync(
+        &mut self,
+        limit: u32,
+        _period: Duration,
+        remaining: u32,
+        reset_after: Duration,
+        now: Instant,
+    ) {
+        self.reset_at = Some(now + reset_after);
+        self.count = limit.saturating_sub(remaining);
+    }
+
+    fn box_clone(&self) -> Box<dyn RateLimitStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// Sliding log strategy
+///
+/// Keep the time of every request sent during the last `period`,
+/// and allow a new one only if less than `limit` requests are logged.
+#[derive(Debug, Clone, Default)]
+pub struct SlidingLog {
+    log: VecDeque<Instant>,
+}
+
+impl SlidingLog {
+    /// Remove the requests older than `period`
+    fn expire(&mut self, period: Duration, now: Instant) {
+        while self
+            .log
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= period)
+        {
+            self.log.pop_front();
+        }
+    }
+}
+
+impl RateLimitStrategy for SlidingLog {
+    fn try_acquire(&mut self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        self.expire(period, now);
+        if let Some(wait) = self.wait_time(limit, period, now) {
+            return Some(wait);
+        }
+        self.log.push_back(now);
+        None
+    }
+
+    fn wait_time(&self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        let expired = self
+            .log
+            .iter()
+            .take_while(|sent| now.duration_since(**sent) >= period)
+            .count();
+        let limit = limit.max(1) as usize;
+        if self.log.len() - expired < limit {
+            return None;
+        }
+        let unlocked_at = self.log[self.log.len() - limit] + period;
+        Some(unlocked_at.saturating_duration_since(now))
+    }
+
+    fn remaining(&self, limit: u32, period: Duration, now: Instant) -> u32 {
+        let logged = self
+            .log
+            .iter()
+            .filter(|sent| now.duration_since(**sent) < period)
+            .count();
+        limit.saturating_sub(logged as u32)
+    }
+
+    /// The used budget is logged as sent `period` before the reset,
+    /// so it expires when the server quota resets
+    fn sync(
+        &mut self,
+        limit: u32,
+        period: Duration,
+        remaining: u32,
+        reset_after: Duration,
+        now: Instant,
+    ) {
+        let sent = (now + reset_after).checked_sub(period).unwrap_or(now);
+        self.log.clear();
+        self.log
+            .extend((0..limit.max(1).saturating_sub(remaining)).map(|_| sent));
+    }
+
+    fn box_clone(&self) -> Box<dyn RateLimitStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// Token bucket strategy
+///
+/// The bucket holds up to `limit` tokens and starts full.\
+/// Tokens are refilled continuously at a rate of `limit` per `period`,
+/// and each request consumes one token.
+#[derive(Debug, Clone, Default)]
+pub struct TokenBucket {
+    tokens: f64,
+    last_refill: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Tokens in the bucket at `now`
+    ///
+    /// The refill starts at `last_refill`, which is in the future after a server sync
+    fn tokens_at(&self, limit: u32, period: Duration, now: Instant) -> f64 {
+        let limit = limit.max(1);
+        match self.last_refill {
+            None => limit as f64,
+            Some(last) => {
+                let refill =
+                    now.duration_since(last).as_secs_f64() * limit as f64 / period.as_secs_f64();
+                (self.tokens + refill).min(limit as f64)
+            }
+        }
+    }
+}
+
+impl RateLimitStrategy for TokenBucket {
+    fn try_acquire(&mut self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        if let Some(wait) = self.wait_time(limit, period, now) {
+            return Some(wait);
+        }
+        self.tokens = self.tokens_at(limit, period, now) - 1.;
+        self.last_refill = Some(self.last_refill.map_or(now, |last| last.max(now)));
+        None
+    }
+
+    fn wait_time(&self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        let tokens = self.tokens_at(limit, period, now);
+        if tokens >= 1. {
+            return None;
+        }
+        let refill_start = self.last_refill.map_or(now, |last| last.max(now));
+        let missing = 1. - tokens;
+        Some(
+            refill_start.duration_since(now)
+                + Duration::from_secs_f64(missing * period.as_secs_f64() / limit.max(1) as f64),
+        )
+    }
+
+    fn remaining(&self, limit: u32, period: Duration, now: Instant) -> u32 {
+        self.tokens_at(limit, period, now) as u32
+    }
+
+    /// An empty bucket gets its next token when the server quota resets
+    fn sync(
+        &mut self,
+        limit: u32,
+        period: Duration,
+        remaining: u32,
+        reset_after: Duration,
+        now: Instant,
+    ) {
+        let limit = limit.max(1);
+        self.tokens = remaining.min(limit) as f64;
+        self.last_refill = match remaining {
+            0 => Some(
+                (now + reset_after)
+                    .checked_sub(period / limit)
+                    .map_or(now, |start| start.max(now)),
+            ),
+            _ => Some(now),
+        };
+    }
+
+    fn box_clone(&self) -> Box<dyn RateLimitStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// Generic Cell Rate Algorithm strategy
+///
+/// Track the theoretical arrival time of the next request.\
+/// Requests are spaced by `period / limit`, with a burst tolerance of `limit` requests.
+#[derive(Debug, Clone, Default)]
+pub struct Gcra {
+    theoretical_arrival: Option<Instant>,
+}
+
+impl Gcra {
+    /// Time between two requests at the sustained rate
+    fn emission_interval(limit: u32, period: Duration) -> Duration {
+        period / limit.max(1)
+    }
+}
+
+impl RateLimitStrategy for Gcra {
+    fn try_acquire(&mut self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        if let Some(wait) = self.wait_time(limit, period, now) {
+            return Some(wait);
+        }
+        let arrival = self.theoretical_arrival.unwrap_or(now).max(now);
+        self.theoretical_arrival = Some(arrival + Self::emission_interval(limit, period));
+        None
+    }
+
+    fn wait_time(&self, limit: u32, period: Duration, now: Instant) -> Option<Duration> {
+        let tolerance = period.saturating_sub(Self::emission_interval(limit, period));
+        let arrival = self.theoretical_arrival.unwrap_or(now).max(now);
+        let ahead = arrival.duration_since(now);
+        (ahead > tolerance).then(|| ahead - tolerance)
+    }
+
+    fn remaining(&self, limit: u32, period: Duration, now: Instant) -> u32 {
+        let limit = limit.max(1);
+        let Some(arrival) = self.theoretical_arrival else {
+            return limit;
+        };
+        let ahead = arrival.saturating_duration_since(now);
+        let used = ahead.as_secs_f64() / Self::emission_interval(limit, period).as_secs_f64();
+        limit.saturating_sub(used.ceil() as u32)
+    }
+
+    fn sync(
+        &mut self,
+        limit: u32,
+        period: Duration,
+        remaining: u32,
+        reset_after: Duration,
+        now: Instant,
+    ) {
+        let limit = limit.max(1);
+        let emission_interval = Self::emission_interval(limit, period);
+        self.theoretical_arrival = Some(match remaining {
+            0 => now + reset_after + period.saturating_sub(emission_interval),
+            _ => now + emission_interval * limit.saturating_sub(remaining),
+        });
+    }
+
+    fn box_clone(&self) -> Box<dyn RateLimitStrategy> {
+        Box::new(self.clone())
+    }
+}
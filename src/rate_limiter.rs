@@ -1,15 +1,21 @@
-use std::{fmt::Display, thread::sleep, time::Duration};
+use std::{fmt::Display, sync::Arc, time::Duration};
 use strum::{EnumIter, IntoEnumIterator};
 
-use chrono::{NaiveDateTime, TimeDelta};
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::HeaderMap;
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::rate_limit_strategy::{FixedWindow, RateLimitStrategy};
 
 /// TimePeriod is used to define the time period for the rate limiter\
-/// It can be Second, Minute, Hour or Day
+/// It can be Second, Minute, Hour, Day or any custom duration
 ///
 /// # Default Api Rate Limiter
 /// ```rust,ignore
 /// // The default rate limiter is 1 request per second
 /// RateLimiter::new(1, TimePeriod::Second)
+/// // 450 requests every 15 minutes
+/// RateLimiter::new(450, TimePeriod::Custom(Duration::from_secs(900)))
 /// ```
 #[derive(Debug, Clone, Default, EnumIter, PartialEq)]
 pub enum TimePeriod {
@@ -18,6 +24,8 @@ pub enum TimePeriod {
     Minute,
     Hour,
     Day,
+    #[strum(disabled)]
+    Custom(Duration),
 }
 impl Display for TimePeriod {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -26,6 +34,7 @@ impl Display for TimePeriod {
             TimePeriod::Minute => write!(f, "minute"),
             TimePeriod::Hour => write!(f, "hourly"),
             TimePeriod::Day => write!(f, "dayly"),
+            TimePeriod::Custom(duration) => write!(f, "{duration:?}"),
         }
     }
 }
@@ -36,18 +45,102 @@ impl From<TimePeriod> for TimeDelta {
             TimePeriod::Minute => Self::minutes(1),
             TimePeriod::Hour => Self::hours(1),
             TimePeriod::Day => Self::days(1),
+            TimePeriod::Custom(duration) => Self::from_std(duration).unwrap_or(Self::MAX),
+        }
+    }
+}
+impl From<Duration> for TimePeriod {
+    /// Use the named period matching the duration, or a custom one
+    fn from(duration: Duration) -> Self {
+        TimePeriod::iter()
+            .find(|period| Duration::from(period.clone()) == duration)
+            .unwrap_or(TimePeriod::Custom(duration))
+    }
+}
+impl From<TimePeriod> for Duration {
+    fn from(val: TimePeriod) -> Self {
+        match val {
+            TimePeriod::Second => Self::from_secs(1),
+            TimePeriod::Minute => Self::from_secs(60),
+            TimePeriod::Hour => Self::from_secs(3600),
+            TimePeriod::Day => Self::from_secs(86400),
+            TimePeriod::Custom(duration) => duration,
         }
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Additional limit enforced by a [RateLimiter] along with its main limit
+///
+/// # Attributes
+/// * limit - The limit of requests per period
+/// * period - The time period of the tier
+/// * strategy - The algorithm used to allow the requests
+#[derive(Debug, Clone)]
+pub struct RateLimitTier {
+    pub limit: u32,
+    pub period: TimePeriod,
+    pub strategy: Box<dyn RateLimitStrategy>,
+}
+impl RateLimitTier {
+    pub fn new(limit: u32, period: impl Into<TimePeriod>) -> Self {
+        Self {
+            limit,
+            period: period.into(),
+            strategy: Box::new(FixedWindow::default()),
+        }
+    }
+
+    /// Set the algorithm used to allow the requests
+    pub fn strategy(mut self, strategy: impl RateLimitStrategy + 'static) -> Self {
+        self.strategy = Box::new(strategy);
+        self
+    }
+}
+
+/// Rate limiter shared by the requests of an API
+///
+/// The limiter is meant to be wrapped in an `Arc<tokio::sync::Mutex<RateLimiter>>`,
+/// and shared with [RateLimiter::acquire_shared].\
+/// The mutex is released while waiting, so the responses keep updating the limiter,
+/// and concurrent requests are served in the order they started waiting.
+///
+/// The algorithm deciding when a request is allowed is a [RateLimitStrategy],
+/// [FixedWindow] by default.
+///
+/// Additional [tiers](RateLimitTier) can be enforced at the same time,
+/// a request is then sent once every tier allows it.
+///
+/// # Example
+/// ```rust,ignore
+/// // 10 requests per second and 10 000 requests per day
+/// RateLimiter::new(10, TimePeriod::Second).tier(10_000, TimePeriod::Day)
+/// ```
+#[derive(Debug, Clone)]
 pub struct RateLimiter {
     pub limit: u32,
-    pub remaining: u32,
     pub period: TimePeriod,
     pub is_asleep: bool,
     pub is_adaptive: bool,
-    pub timer: NaiveDateTime,
+    pub strategy: Box<dyn RateLimitStrategy>,
+    pub tiers: Vec<RateLimitTier>,
+    queue: WaitQueue,
+}
+
+/// Queue of the requests waiting for a shared limiter, in the order they started waiting
+///
+/// A cloned limiter gets its own queue
+#[derive(Debug, Default)]
+struct WaitQueue(Arc<Mutex<()>>);
+
+impl Clone for WaitQueue {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(1, TimePeriod::Second)
+    }
 }
 impl RateLimiter {
     /// Create a new rate limiter
@@ -56,17 +149,50 @@ impl RateLimiter {
     /// * limit - The limit of requests per period
     /// * period - The time period for the rate limiter
     /// * is_adaptive - If the rate limiter should adapt to the server rate limit
+    /// * strategy - [FixedWindow]
+    /// * tiers - No additional tier
     pub fn new(limit: u32, period: TimePeriod) -> RateLimiter {
         RateLimiter {
             limit,
-            remaining: limit,
             period,
             is_asleep: false,
             is_adaptive: true,
-            timer: chrono::Utc::now().naive_local(),
+            strategy: Box::new(FixedWindow::default()),
+            tiers: Vec::new(),
+            queue: WaitQueue::default(),
         }
     }
 
+    /// Set the algorithm used to allow the requests
+    pub fn strategy(mut self, strategy: impl RateLimitStrategy + 'static) -> Self {
+        self.strategy = Box::new(strategy);
+        self
+    }
+
+    /// Add a limit enforced along with the main one, using the [FixedWindow] strategy
+    pub fn tier(self, limit: u32, period: impl Into<TimePeriod>) -> Self {
+        self.tier_with(RateLimitTier::new(limit, period))
+    }
+
+    /// Add a limit enforced along with the main one
+    pub fn tier_with(mut self, tier: RateLimitTier) -> Self {
+        self.tiers.push(tier);
+        self
+    }
+
+    /// Number of requests that can be sent right now without waiting,
+    /// according to the most restrictive tier
+    pub fn remaining(&self) -> u32 {
+        let now = Instant::now();
+        self.limits()
+            .map(|(limit, period, strategy)| match limit {
+                0 => 0,
+                _ => strategy.remaining(limit, period, now),
+            })
+            .min()
+            .unwrap_or_default()
+    }
+
     /// Set the rate limiter to be adaptive or not
     ///
     /// If the rate limiter is adaptive, it will adapt to the server rate limit,
@@ -76,55 +202,302 @@ impl RateLimiter {
         self
     }
 
+    /// Main limit followed by the additional tiers
+    fn limits(&self) -> impl Iterator<Item = (u32, Duration, &dyn RateLimitStrategy)> {
+        std::iter::once((
+            self.limit,
+            self.period.clone().into(),
+            self.strategy.as_ref(),
+        ))
+        .chain(self.tiers.iter().map(|tier| {
+            (
+                tier.limit,
+                tier.period.clone().into(),
+                tier.strategy.as_ref(),
+            )
+        }))
+    }
+
+    /// Mutable main limit followed by the additional tiers
+    fn limits_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (&mut u32, &TimePeriod, &mut Box<dyn RateLimitStrategy>)> {
+        std::iter::once((&mut self.limit, &self.period, &mut self.strategy)).chain(
+            self.tiers
+                .iter_mut()
+                .map(|tier| (&mut tier.limit, &tier.period, &mut tier.strategy)),
+        )
+    }
+
     /// Update the rate limiter with the headers from the request
-    pub fn update(&mut self, headers: &reqwest::header::HeaderMap) {
+    ///
+    /// # Supported headers
+    /// * `x-{secondly|minute|hourly|dayly}-ratelimit-limit`
+    /// * `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` and `RateLimit-Policy` (IETF draft)
+    /// * `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset` (epoch or delta seconds)
+    /// * `Retry-After` (delta seconds or HTTP-date)
+    ///
+    /// Each limit with a period fills the tier with the same period, a new tier is added if none matches.\
+    /// Limits only decrease, and the remaining budget and the next reset
+    /// are synchronized with the ones reported by the server.
+    pub fn update(&mut self, headers: &HeaderMap) {
         if !self.is_adaptive {
             return;
         }
+        self.update_period_limit(headers);
+        let reported = RateLimitHeaders::from_headers(headers);
+        self.update_reported(&reported);
+    }
+
+    /// Fill the tier with the given period, or add a new tier
+    fn fill_tier(&mut self, limit: u32, period: TimePeriod) {
+        let tier_limit = if self.period == period {
+            &mut self.limit
+        } else {
+            match self.tiers.iter_mut().find(|tier| tier.period == period) {
+                Some(tier) => &mut tier.limit,
+                None => {
+                    self.tiers.push(RateLimitTier::new(limit, period));
+                    return;
+                }
+            }
+        };
+        if limit < *tier_limit {
+            *tier_limit = limit;
+        }
+    }
+
+    /// Update the limits from the `x-{period}-ratelimit-limit` headers
+    fn update_period_limit(&mut self, headers: &HeaderMap) {
         for period in TimePeriod::iter() {
             if let Some(limit) = headers.get(format!("x-{period}-ratelimit-limit")) {
-                let Ok(limit) = limit.to_str().unwrap().parse::<u32>() else {
-                    return;
+                let Some(limit) = limit.to_str().ok().and_then(|l| l.parse::<u32>().ok()) else {
+                    log::error!("Invalid x-{period}-ratelimit-limit header: {limit:?}");
+                    continue;
                 };
-                if self.period == period && limit < self.limit {
-                    self.limit = limit;
-                    return;
-                }
-                self.period = period;
-                if limit < self.limit {
-                    self.limit = limit;
+                self.fill_tier(limit, period);
+            }
+        }
+    }
+
+    /// Update the rate limiter with the standard headers reported by the server
+    ///
+    /// The limits are only lowered by the policies, a reported limit without its period is not applied.\
+    /// The remaining budget is synchronized on the tier with the reported limit,
+    /// and the period of its policy if any. It is ignored if no tier matches the reported quota
+    fn update_reported(&mut self, reported: &RateLimitHeaders) {
+        for policy in &reported.policies {
+            self.fill_tier(policy.limit, policy.window.into());
+        }
+
+        let now = Instant::now();
+        if let Some(retry_after) = reported.retry_after {
+            log::info!("Server asked to retry after {retry_after:?}");
+            for (limit, period, strategy) in self.limits_mut() {
+                if *limit > 0 {
+                    strategy.sync(*limit, period.clone().into(), 0, retry_after, now);
                 }
+            }
+        } else if let (Some(remaining), Some(reported_limit)) = (reported.remaining, reported.limit)
+        {
+            let window: Option<TimePeriod> = reported
+                .policies
+                .iter()
+                .find(|policy| policy.limit == reported_limit)
+                .map(|policy| policy.window.into());
+            let Some((limit, period, strategy)) = self.limits_mut().find(|(limit, period, _)| {
+                **limit == reported_limit && window.as_ref().is_none_or(|window| window == *period)
+            }) else {
+                log::debug!("Reported quota of {reported_limit} matches no tier, ignored");
+                return;
+            };
+            if *limit == 0 {
                 return;
             }
+            let period: Duration = period.clone().into();
+            let reset_after = reported.reset.unwrap_or(period);
+            strategy.sync(*limit, period, remaining, reset_after, now);
         }
     }
 
-    /// Sleep for the period of the rate limiter
-    fn sleep(&self) {
-        match self.period {
-            TimePeriod::Second => sleep(Duration::from_secs(1)),
-            TimePeriod::Minute => sleep(Duration::from_secs(60)),
-            TimePeriod::Hour => sleep(Duration::from_secs(3600)),
-            TimePeriod::Day => sleep(Duration::from_secs(86400)),
+    /// Consume one request from every tier if they all allow it
+    ///
+    /// Returns the time to wait for the most restrictive tier otherwise
+    fn try_acquire(&mut self, now: Instant) -> Option<Duration> {
+        let wait = self
+            .limits()
+            .filter_map(|(limit, period, strategy)| match limit {
+                0 => Some(period),
+                _ => strategy.wait_time(limit, period, now),
+            })
+            .max();
+        if wait.is_some() {
+            return wait;
+        }
+        for (limit, period, strategy) in self.limits_mut() {
+            strategy.try_acquire(*limit, period.clone().into(), now);
         }
+        None
     }
 
-    /// Request once the rate limit is available
-    pub fn request(&mut self) {
+    /// Wait until the rate limit is available, then consume one request
+    ///
+    /// The wait relies on `tokio::time` and never blocks the runtime worker.\
+    /// It ends as soon as every tier has capacity again.
+    pub async fn acquire(&mut self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            self.is_asleep = true;
+            log::info!("Rate limit exceeded, waiting for {wait:?}");
+            tokio::time::sleep(wait).await;
+            self.is_asleep = false;
+        }
+    }
+
+    /// Wait until a shared limiter is available, then consume one request
+    ///
+    /// The mutex is only held to compute the wait, and released while sleeping,
+    /// so the responses of the requests in flight and the setters of the API can update the limiter.\
+    /// The limit is checked again after each wait.
+    pub async fn acquire_shared(limiter: &Mutex<RateLimiter>) {
+        let queue = limiter.lock().await.queue.0.clone();
+        let _turn = queue.lock().await;
         loop {
-            if chrono::Utc::now().naive_local() - self.timer >= self.period.clone().into() {
-                self.timer = chrono::Utc::now().naive_local();
-                self.remaining = self.limit;
-            }
-            if self.remaining > 0 {
-                self.remaining -= 1;
+            let wait = {
+                let mut limiter = limiter.lock().await;
+                let wait = limiter.try_acquire(Instant::now());
+                limiter.is_asleep = wait.is_some();
+                wait
+            };
+            let Some(wait) = wait else {
                 return;
-            } else {
-                self.is_asleep = true;
-                log::info!("Rate limit exceeded, sleeping for {:?}", self.period);
-                self.sleep();
-                self.is_asleep = false;
+            };
+            log::info!("Rate limit exceeded, waiting for {wait:?}");
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Rate limit policy advertised by the `RateLimit-Policy` header
+///
+/// Example: `100;w=60` or `"default";q=100;w=60`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RateLimitPolicy {
+    pub(crate) name: Option<String>,
+    pub(crate) limit: u32,
+    pub(crate) window: Duration,
+}
+
+impl RateLimitPolicy {
+    /// Parse every policy of a `RateLimit-Policy` header value
+    fn parse_all(value: &str) -> Vec<RateLimitPolicy> {
+        value.split(',').filter_map(Self::parse).collect()
+    }
+
+    fn parse(item: &str) -> Option<RateLimitPolicy> {
+        let mut parts = item.split(';').map(str::trim);
+        let first = parts.next()?;
+        let (name, mut limit) = match first.parse::<u32>() {
+            Ok(limit) => (None, Some(limit)),
+            Err(_) => (Some(first.trim_matches('"').to_string()), None),
+        };
+        let mut window = None;
+        for param in parts {
+            match param.split_once('=') {
+                Some(("q", value)) => limit = value.parse().ok(),
+                Some(("w", value)) => window = value.parse().ok().map(Duration::from_secs),
+                _ => {}
             }
         }
+        Some(RateLimitPolicy {
+            name,
+            limit: limit?,
+            window: window?,
+        })
+    }
+}
+
+/// Rate limit information reported by the server in the response headers
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RateLimitHeaders {
+    pub(crate) limit: Option<u32>,
+    pub(crate) remaining: Option<u32>,
+    pub(crate) reset: Option<Duration>,
+    pub(crate) retry_after: Option<Duration>,
+    pub(crate) policies: Vec<RateLimitPolicy>,
+}
+
+impl RateLimitHeaders {
+    /// Name of the quota the headers are about
+    ///
+    /// Read from `X-RateLimit-Resource`, or from the first named `RateLimit-Policy`
+    pub(crate) fn resource(headers: &HeaderMap) -> Option<String> {
+        if let Some(resource) = header_str(headers, "x-ratelimit-resource") {
+            return Some(resource.to_string());
+        }
+        header_str(headers, "ratelimit-policy")
+            .map(RateLimitPolicy::parse_all)
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|policy| policy.name)
+    }
+
+    /// Read the IETF draft headers first, then the `X-RateLimit-*` ones
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let number = |names: [&str; 2]| {
+            names
+                .iter()
+                .find_map(|name| header_str(headers, name))
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+        let now = Utc::now();
+        let reset = match header_str(headers, "ratelimit-reset") {
+            Some(value) => value.parse::<u64>().ok().map(Duration::from_secs),
+            None => header_str(headers, "x-ratelimit-reset")
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(|reset| reset_delay(reset, now)),
+        };
+        Self {
+            limit: number(["ratelimit-limit", "x-ratelimit-limit"])
+                .map(|limit| limit.min(u32::MAX as u64) as u32),
+            remaining: number(["ratelimit-remaining", "x-ratelimit-remaining"])
+                .map(|remaining| remaining.min(u32::MAX as u64) as u32),
+            reset,
+            retry_after: header_str(headers, "retry-after")
+                .and_then(|value| retry_after_delay(value, now)),
+            policies: header_str(headers, "ratelimit-policy")
+                .map(RateLimitPolicy::parse_all)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+}
+
+/// Values above this threshold are Unix timestamps instead of delta seconds
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// Delay until a `X-RateLimit-Reset` value, given as a Unix timestamp or as delta seconds
+fn reset_delay(reset: u64, now: DateTime<Utc>) -> Duration {
+    if reset < EPOCH_THRESHOLD {
+        return Duration::from_secs(reset);
+    }
+    Duration::from_secs(reset.saturating_sub(now.timestamp().max(0) as u64))
+}
+
+/// Delay of a `Retry-After` value, given as delta seconds or as an HTTP-date
+fn retry_after_delay(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
     }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
 }
@@ -1,20 +1,34 @@
-use reqwest::{Client, Method, StatusCode, Url, header::HeaderMap};
+use reqwest::{
+    Client, Method, StatusCode, Url,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::Value;
 use std::{
+    fmt::Debug,
     future::{Future, IntoFuture},
     pin::Pin,
-    sync::{Arc, RwLock},
+    sync::Arc,
 };
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use url::Position;
+use uuid::Uuid;
 
 use crate::{
-    error::{ApiError, Result},
+    circuit_breaker::CircuitBreaker,
+    digest_auth::{DigestAuth, DigestChallenge},
+    error::{ApiError, HttpError, ParseError, Result},
+    error_decoder::{ErrorDecoder, JsonErrorDecoder},
     filter::{Filter, FilterRule},
+    oauth2::TokenSource,
     pagination::{Pagination, PaginationRule, RequestPagination},
-    query::Query,
+    query::{Query, TryIntoQuery},
     range::{Range, RangeRule},
+    rate_limit_bucket::RateLimitBuckets,
     rate_limiter::RateLimiter,
+    request_signer::RequestSigner,
     request_url::RequestUrl,
+    retry_policy::{IDEMPOTENCY_KEY, RetryPolicy},
     sort::{Sort, SortOrder, SortRule},
 };
 
@@ -30,6 +44,16 @@ use crate::{
 /// * headers - Headers to be used in the request
 /// * body - body to be used in the request
 /// * pagination - Pagination type to be used in the request
+/// * retry_policy - Policy to retry the failed attempts of the request
+/// * idempotency_header - Header carrying the idempotency key of the retried POST and PATCH requests
+/// * idempotency_key - Key generated on the first send, the same for every attempt
+/// * client - HTTP client used to send the request
+/// * concurrency - Semaphore limiting the requests in flight, shared with the API
+/// * circuit_breaker - Circuit breaker of the API, failing fast while the host is down
+/// * error_decoder - Decoder of the error responses, returned as `ApiError::Api`
+/// * token_source - Source of the access token sent with every attempt
+/// * signer - Signer of every attempt
+/// * digest - Digest credentials, answering the challenges of the server
 #[derive(Debug, Clone)]
 pub struct Request<
     X: Deserialize<'static> = (),
@@ -49,9 +73,19 @@ pub struct Request<
     pub(crate) filter: F,
     pub(crate) sort: S,
     pub(crate) range: R,
-    pub(crate) rate_limiter: Arc<RwLock<RateLimiter>>,
-    pub(crate) force_limit: Option<u8>,
-    pub(crate) _phantom: std::marker::PhantomData<X>,
+    pub(crate) rate_limiter: Arc<Mutex<RateLimiter>>,
+    pub(crate) rate_limit_buckets: RateLimitBuckets,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) idempotency_header: HeaderName,
+    pub(crate) idempotency_key: Option<String>,
+    pub(crate) client: Client,
+    pub(crate) concurrency: Option<Arc<Semaphore>>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) error_decoder: Option<Arc<dyn ErrorDecoder>>,
+    pub(crate) token_source: Option<Arc<TokenSource>>,
+    pub(crate) signer: Option<Arc<dyn RequestSigner>>,
+    pub(crate) digest: Option<Arc<DigestAuth>>,
+    pub(crate) _phantom: std::marker::PhantomData<fn() -> X>,
 }
 
 impl<
@@ -94,8 +128,18 @@ where
             filter: F::default(),
             sort: S::default(),
             range: R::default(),
-            rate_limiter: Arc::new(RwLock::new(RateLimiter::default())),
-            force_limit: None,
+            rate_limiter: Arc::new(Mutex::new(RateLimiter::default())),
+            rate_limit_buckets: RateLimitBuckets::default(),
+            retry_policy: RetryPolicy::default(),
+            idempotency_header: IDEMPOTENCY_KEY,
+            idempotency_key: None,
+            client: Client::new(),
+            concurrency: None,
+            circuit_breaker: None,
+            error_decoder: None,
+            token_source: None,
+            signer: None,
+            digest: None,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -117,22 +161,20 @@ where
         T: DeserializeOwned + Serialize,
         B: DeserializeOwned + Serialize,
     {
-        match self.rate_limiter.write() {
-            Ok(mut rate) => rate.request(),
-            Err(e) => log::error!("Rate limiter error: {e:?}"),
-        }
-        let request = self.build_reqwest::<B>(self.body.clone())?;
-        log::info!("{request:?}");
-        let first_response = Self::execute_reqwest(&request, self.force_limit).await?;
-        match self.rate_limiter.write() {
-            Ok(mut rate) => rate.update(first_response.headers()),
-            Err(e) => log::error!("Rate limiter error: {e:?}"),
-        }
+        let mut request = self.build_reqwest::<B>(self.body.clone())?;
+        self.add_idempotency_key(&mut request)?;
+        log::info!(
+            "{} {}",
+            request.method(),
+            self.request_url.redact(request.url())
+        );
+        let slot = Self::acquire_slot(&self.concurrency).await;
+        let first_response = self.execute_reqwest(&request).await?;
         let number_of_elements = Self::get_number_of_elements(first_response.headers());
         match number_of_elements {
-            1 => Self::parse_response(first_response).await,
+            1 => self.parse_response(first_response).await,
             _ => {
-                self.parse_response_array::<T>(request, first_response)
+                self.parse_response_array::<T>(request, first_response, slot)
                     .await
             }
         }
@@ -150,11 +192,10 @@ where
             None => Vec::new(),
         };
 
-        let client = Client::new();
         let url =
             self.request_url
                 .as_url(&self.pagination, &self.filter, &self.sort, &self.range)?;
-        let mut request_builder = client.request(self.method.clone(), url).body(body);
+        let mut request_builder = self.client.request(self.method.clone(), url).body(body);
         if let Some(headers) = &self.headers {
             request_builder = request_builder.headers(headers.clone());
         }
@@ -164,19 +205,38 @@ where
         }
     }
 
+    /// Add the idempotency key to a POST or PATCH request that can be retried
+    ///
+    /// The key is generated once, so every attempt and every send of the request share it.\
+    /// A key already set in the headers of the request is kept.
+    fn add_idempotency_key(&mut self, request: &mut reqwest::Request) -> Result<()> {
+        if !self.retry_policy.needs_idempotency_key(request.method())
+            || request.headers().contains_key(&self.idempotency_header)
+        {
+            return Ok(());
+        }
+        let key = self
+            .idempotency_key
+            .get_or_insert_with(|| Uuid::new_v4().to_string());
+        request
+            .headers_mut()
+            .insert(self.idempotency_header.clone(), HeaderValue::from_str(key)?);
+        Ok(())
+    }
+
     fn build_next_reqwest(
+        client: &Client,
         previous_request: &reqwest::Request,
         url: Url,
     ) -> Result<reqwest::Request> {
         let request = reqwest::Request::new(previous_request.method().clone(), url);
-        let client = Client::new();
-        let mut request = reqwest::RequestBuilder::from_parts(client, request)
+        let mut request = reqwest::RequestBuilder::from_parts(client.clone(), request)
             .headers(previous_request.headers().to_owned());
-        let body: Vec<u8> = match previous_request.body() {
-            Some(p) => p.as_bytes().unwrap().to_owned(),
-            None => Vec::new(),
-        };
-        request = request.body(body);
+        if let Some(body) = previous_request.body() {
+            // A streaming body cannot be sent again for the next pages
+            let body = body.as_bytes().ok_or(ApiError::ReqwestClone)?;
+            request = request.body(body.to_owned());
+        }
 
         match request.build() {
             Ok(request) => Ok(request),
@@ -184,40 +244,176 @@ where
         }
     }
 
-    async fn execute_reqwest(
-        request: &reqwest::Request,
-        retries_limit: Option<u8>,
-    ) -> Result<reqwest::Response> {
-        let client = Client::new();
-        let response = client
-            .execute(request.try_clone().ok_or(ApiError::ReqwestClone)?)
-            .await
-            .map_err(ApiError::ReqwestExecute)?;
-
-        if response.status() == StatusCode::TOO_MANY_REQUESTS {
-            let Some(mut limit) = retries_limit else {
-                return Err(ApiError::TooManyRequests);
+    /// Execute the request and update the rate limiter with the response headers
+    ///
+    /// The rate limiter is updated before the status is checked,
+    /// so a `Retry-After` on an error response is respected by the next requests.\
+    /// If the server names the quota of a rate limit bucket, that bucket is updated instead.\
+    /// Failed attempts are retried according to the retry policy,
+    /// and every attempt waits for the rate limit and goes through the circuit breaker.\
+    /// Error responses are decoded with the error decoder.
+    async fn execute_reqwest(&self, request: &reqwest::Request) -> Result<reqwest::Response> {
+        let mut attempt = 1;
+        let response = loop {
+            RateLimiter::acquire_shared(&self.rate_limiter).await;
+            let result = self.execute_once(request).await;
+            let (delay, reason) = match &result {
+                Ok(response) => (
+                    self.retry_policy.delay_for_status(
+                        request.method(),
+                        response.status(),
+                        response.headers(),
+                        attempt,
+                    ),
+                    response.status().to_string(),
+                ),
+                Err(ApiError::ReqwestExecute(e)) => (
+                    self.retry_policy
+                        .delay_for_error(request.method(), e, attempt),
+                    e.to_string(),
+                ),
+                Err(_) => (None, String::new()),
             };
-            while limit > 0 {
-                limit -= 1;
-                let response = client
-                    .execute(request.try_clone().ok_or(ApiError::ReqwestClone)?)
-                    .await
-                    .map_err(ApiError::ReqwestExecute)?;
-                if response.status() != StatusCode::TOO_MANY_REQUESTS {
-                    break;
-                }
-            }
-        }
+            let Some(delay) = delay else {
+                break result?;
+            };
+            log::info!(
+                "Attempt {attempt}/{} of {} {} failed ({reason}), retrying in {delay:?}",
+                self.retry_policy.max_attempts,
+                request.method(),
+                self.request_url.redact(request.url())
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
         match response.status() {
             StatusCode::OK
             | StatusCode::CREATED
             | StatusCode::ACCEPTED
             | StatusCode::NO_CONTENT => Ok(response),
-            status => Err(status.into()),
+            _ => {
+                let url = self.request_url.redact(response.url());
+                let mut error = HttpError::from_response(request.method().clone(), response).await;
+                error.url = url;
+                Err(error.decode(self.error_decoder.as_deref()))
+            }
         }
     }
 
+    /// Send one attempt of the request
+    ///
+    /// Transport errors and 5xx responses are recorded as failures by the circuit breaker
+    async fn execute_once(&self, request: &reqwest::Request) -> Result<reqwest::Response> {
+        let host = &request.url()[Position::BeforeHost..Position::AfterPort];
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.try_acquire(host)?;
+        }
+        let result = self.send_authorized(request).await;
+        if let Some(breaker) = &self.circuit_breaker {
+            let success = matches!(&result, Ok(response) if !response.status().is_server_error());
+            breaker.record(host, success);
+        }
+        let response = result?;
+        self.rate_limit_buckets
+            .update(&self.rate_limiter, response.headers())
+            .await;
+        Ok(response)
+    }
+
+    /// Send the request with the access token of the token source
+    ///
+    /// A 401 response invalidates the token, and the request is sent once more with a new token
+    async fn send_authorized(&self, request: &reqwest::Request) -> Result<reqwest::Response> {
+        if let Some(digest) = &self.digest {
+            return self.send_digest(request, digest).await;
+        }
+        let Some(source) = &self.token_source else {
+            return self.send_with_token(request, None).await;
+        };
+        let token = source.token().await?;
+        let response = self.send_with_token(request, Some(&token)).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        log::info!(
+            "{} {} was rejected with the current token, retrying with a new one",
+            request.method(),
+            self.request_url.redact(request.url())
+        );
+        source.invalidate(&token).await;
+        let token = source.token().await?;
+        self.send_with_token(request, Some(&token)).await
+    }
+
+    /// Send the request with the Digest credentials
+    ///
+    /// A 401 challenge is answered by sending the request once more,
+    /// unless the credentials of the current challenge were rejected and its nonce is not stale
+    async fn send_digest(
+        &self,
+        request: &reqwest::Request,
+        digest: &DigestAuth,
+    ) -> Result<reqwest::Response> {
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .unwrap_or_default();
+        let authorization = digest.authorization(request.method(), request.url(), body)?;
+        let answered = authorization.is_some();
+        let response = self.send_with_authorization(request, authorization).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(challenge) = DigestChallenge::from_headers(response.headers()) else {
+            return Ok(response);
+        };
+        if answered && !challenge.stale {
+            return Ok(response);
+        }
+        log::info!(
+            "{} {} was challenged by the realm {}, retrying with Digest credentials",
+            request.method(),
+            self.request_url.redact(request.url()),
+            challenge.realm
+        );
+        digest.set_challenge(challenge);
+        let authorization = digest.authorization(request.method(), request.url(), body)?;
+        self.send_with_authorization(request, authorization).await
+    }
+
+    async fn send_with_token(
+        &self,
+        request: &reqwest::Request,
+        token: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let authorization = match token {
+            Some(token) => Some(HeaderValue::from_str(&format!("Bearer {token}"))?),
+            None => None,
+        };
+        self.send_with_authorization(request, authorization).await
+    }
+
+    async fn send_with_authorization(
+        &self,
+        request: &reqwest::Request,
+        authorization: Option<HeaderValue>,
+    ) -> Result<reqwest::Response> {
+        let mut attempt = request.try_clone().ok_or(ApiError::ReqwestClone)?;
+        if let Some(authorization) = authorization {
+            attempt
+                .headers_mut()
+                .insert(reqwest::header::AUTHORIZATION, authorization);
+        }
+        // The signature covers the final URL and headers of this attempt
+        if let Some(signer) = &self.signer {
+            signer.sign(&mut attempt)?;
+        }
+        self.client
+            .execute(attempt)
+            .await
+            .map_err(ApiError::ReqwestExecute)
+    }
+
     fn get_page_count(headers: &HeaderMap, pagination: &PaginationRule) -> usize {
         let page_count = match headers
             .get("X-Total")
@@ -241,55 +437,124 @@ where
         }
     }
 
-    async fn parse_response<T>(response: reqwest::Response) -> Result<T>
+    /// Parse the body of the response into `T`
+    ///
+    /// On failure, the error gives the JSON path of the failing field and an excerpt of the body
+    async fn parse_response<T>(&self, response: reqwest::Response) -> Result<T>
     where
-        T: DeserializeOwned + Serialize,
+        T: DeserializeOwned,
     {
+        let url = self.request_url.redact(response.url());
         let text = match response.text().await {
             Ok(text) => text,
             Err(e) => return Err(ApiError::ResponseToText(e)),
         };
-        serde_json::from_slice::<T>(text.as_bytes()).map_err(ApiError::ResponseParse)
+        let mut deserializer = serde_json::Deserializer::from_str(&text);
+        let parsed = serde_path_to_error::deserialize::<_, T>(&mut deserializer)
+            .map_err(|e| (e.path().to_string(), e.into_inner()))
+            .and_then(|parsed| match deserializer.end() {
+                Ok(()) => Ok(parsed),
+                Err(e) => Err((String::from("."), e)),
+            });
+        parsed.map_err(|(path, e)| ParseError::from_body::<T>(path, e, url, &text).into())
     }
 
+    /// Wait for a free slot if the API limits the number of requests in flight
+    ///
+    /// The slot is released when the returned permit is dropped
+    async fn acquire_slot(concurrency: &Option<Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+        match concurrency {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Collect every page of the response
+    ///
+    /// Each page holds a concurrency slot until its body is read,
+    /// starting with the slot of the first page
     async fn parse_response_array<T>(
         &mut self,
         request: reqwest::Request,
         first_response: reqwest::Response,
+        first_slot: Option<OwnedSemaphorePermit>,
     ) -> Result<T>
     where
         T: DeserializeOwned + Serialize,
     {
         let page_count =
             Self::get_page_count(first_response.headers(), self.pagination.pagination());
+        let url = self.request_url.redact(first_response.url());
+        // Page number and index of the first item of each page
+        let mut pages = vec![(self.pagination.current_page(), 0)];
         self.pagination.next();
-        let mut json_values = Value::Array(Self::parse_response(first_response).await?);
+        let mut json_values = Value::Array(self.parse_response(first_response).await?);
+        drop(first_slot);
 
         for _ in 1..page_count {
             let next_url =
                 self.request_url
                     .as_url(&self.pagination, &self.filter, &self.sort, &self.range)?;
 
-            let next_request = Self::build_next_reqwest(&request, next_url)?;
-            log::info!("{next_request:?}");
-
-            let next_page_response = Self::execute_reqwest(&next_request, self.force_limit).await?;
-            match self.rate_limiter.write() {
-                Ok(mut rate) => rate.update(next_page_response.headers()),
-                Err(e) => log::error!("Rate limiter error: {e:?}"),
+            let mut next_request = Self::build_next_reqwest(&self.client, &request, next_url)?;
+            if self.idempotency_key.is_some() {
+                // Each page is a distinct operation for the server
+                next_request.headers_mut().insert(
+                    self.idempotency_header.clone(),
+                    HeaderValue::from_str(&Uuid::new_v4().to_string())?,
+                );
             }
+            log::info!(
+                "{} {}",
+                next_request.method(),
+                self.request_url.redact(next_request.url())
+            );
+
+            let _slot = Self::acquire_slot(&self.concurrency).await;
+            let next_page_response = self.execute_reqwest(&next_request).await?;
 
             match &mut json_values {
                 Value::Array(a) => {
                     let mut json_value: Vec<Value> =
-                        Self::parse_response(next_page_response).await?;
+                        self.parse_response(next_page_response).await?;
+                    pages.push((self.pagination.current_page(), a.len()));
                     a.append(&mut json_value)
                 }
                 _ => return Err(ApiError::JsonValueNotArray),
             }
             self.pagination.next();
         }
-        serde_json::from_value::<T>(json_values).map_err(ApiError::ResponseParse)
+        serde_path_to_error::deserialize::<_, T>(&json_values)
+            .map_err(|e| Self::page_parse_error::<T>(e, url, &json_values, &pages).into())
+    }
+
+    /// Locate the failing item of a paginated response in its page
+    fn page_parse_error<T>(
+        error: serde_path_to_error::Error<serde_json::Error>,
+        url: String,
+        json_values: &Value,
+        pages: &[(usize, usize)],
+    ) -> ParseError {
+        let index = match error.path().iter().next() {
+            Some(serde_path_to_error::Segment::Seq { index }) => Some(*index),
+            _ => None,
+        };
+        let item = index
+            .and_then(|index| json_values.get(index))
+            .map(Value::to_string)
+            .unwrap_or_default();
+        let error =
+            ParseError::from_body::<T>(error.path().to_string(), error.into_inner(), url, &item);
+        match index.and_then(|index| {
+            pages
+                .iter()
+                .rev()
+                .find(|(_, start)| *start <= index)
+                .map(|(page, start)| (*page, index - start))
+        }) {
+            Some((page, item)) => error.item(page, item),
+            None => error,
+        }
     }
 
     pub fn reset_pagination(&mut self) {
@@ -381,11 +646,24 @@ where
         self
     }
 
+    /// Set the policy to retry the failed attempts of the request
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
     /// Set the number of retry attempts on 429 responses
     ///
     /// None means no retry
+    #[deprecated(note = "use `retry_policy`")]
     pub fn force_limite(mut self, limit: Option<u8>) -> Self {
-        self.force_limit = limit;
+        self.retry_policy = RetryPolicy::force_limit(limit);
+        self
+    }
+
+    /// Set the header carrying the idempotency key of the retried POST and PATCH requests
+    pub fn idempotency_header(mut self, name: HeaderName) -> Self {
+        self.idempotency_header = name;
         self
     }
 
@@ -407,4 +685,45 @@ where
         self.request_url = self.request_url.join_query(query.into());
         self
     }
+
+    /// Add a query that may be invalid to the request, such as a query string
+    ///
+    /// Returns `ApiError::InvalidQuery` if the query cannot be parsed
+    pub fn try_query(mut self, query: impl TryIntoQuery) -> Result<Self> {
+        self.request_url = self.request_url.join_query(query.try_into_query()?);
+        Ok(self)
+    }
+
+    /// Set the HTTP client used to send the request
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send the request through a circuit breaker
+    ///
+    /// Share the circuit breaker between requests to track the health of their hosts together
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Decode the error responses with a decoder, overriding the one of the API
+    pub fn error_decoder(mut self, decoder: impl ErrorDecoder + 'static) -> Self {
+        self.error_decoder = Some(Arc::new(decoder));
+        self
+    }
+
+    /// Decode the error responses as JSON into `E`, overriding the decoder of the API
+    pub fn error_body<E: DeserializeOwned + Debug + Send + Sync + 'static>(self) -> Self {
+        self.error_decoder(JsonErrorDecoder::<E>::new())
+    }
+
+    /// Limit the number of requests in flight with a semaphore
+    ///
+    /// Share the semaphore between requests to apply a common limit
+    pub fn concurrency(mut self, semaphore: Arc<Semaphore>) -> Self {
+        self.concurrency = Some(semaphore);
+        self
+    }
 }
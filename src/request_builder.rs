@@ -1,15 +1,26 @@
-use reqwest::{Method, header::HeaderMap};
+use reqwest::{
+    Client, Method,
+    header::{HeaderMap, HeaderName},
+};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore};
 
 use crate::{
+    circuit_breaker::CircuitBreaker,
+    digest_auth::DigestAuth,
+    error_decoder::ErrorDecoder,
     filter::{Filter, FilterRule},
+    oauth2::TokenSource,
     pagination::{Pagination, PaginationRule, RequestPagination},
     query::Query,
     range::{Range, RangeRule},
+    rate_limit_bucket::RateLimitBuckets,
     rate_limiter::RateLimiter,
     request::Request,
+    request_signer::RequestSigner,
     request_url::RequestUrl,
+    retry_policy::{IDEMPOTENCY_KEY, RetryPolicy},
     sort::{Sort, SortRule},
 };
 
@@ -33,9 +44,18 @@ pub struct RequestBuilder<
     pub(crate) filter: F,
     pub(crate) sort: S,
     pub(crate) range: R,
-    pub(crate) rate_limiter: Arc<RwLock<RateLimiter>>,
-    pub(crate) force_limit: Option<u8>,
-    pub(crate) _phantom: std::marker::PhantomData<X>,
+    pub(crate) rate_limiter: Arc<Mutex<RateLimiter>>,
+    pub(crate) rate_limit_buckets: RateLimitBuckets,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) idempotency_header: HeaderName,
+    pub(crate) client: Option<Client>,
+    pub(crate) concurrency: Option<Arc<Semaphore>>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) error_decoder: Option<Arc<dyn ErrorDecoder>>,
+    pub(crate) token_source: Option<Arc<TokenSource>>,
+    pub(crate) signer: Option<Arc<dyn RequestSigner>>,
+    pub(crate) digest: Option<Arc<DigestAuth>>,
+    pub(crate) _phantom: std::marker::PhantomData<fn() -> X>,
 }
 
 impl<X: Deserialize<'static>, B: Serialize + Clone, P: Pagination, F: Filter, S: Sort, R: Range>
@@ -55,8 +75,17 @@ where
     /// * sort - S::default()
     /// * range - R::default()
     /// * rate_limiter - The rate limiter to use
-    /// * force_limit - None
-    pub fn new(request_url: RequestUrl, rate_limiter: Arc<RwLock<RateLimiter>>) -> Self {
+    /// * rate_limit_buckets - No bucket (Updates go to the rate_limiter)
+    /// * retry_policy - RetryPolicy::default() (No retry)
+    /// * idempotency_header - Idempotency-Key
+    /// * client - None (A new client is created on build)
+    /// * concurrency - None (No limit on the requests in flight)
+    /// * circuit_breaker - None
+    /// * error_decoder - None (Error responses keep the raw text of their body)
+    /// * token_source - None (The authorization headers are static)
+    /// * signer - None (The requests are not signed)
+    /// * digest - None
+    pub fn new(request_url: RequestUrl, rate_limiter: Arc<Mutex<RateLimiter>>) -> Self {
         Self {
             method: Method::GET,
             request_url,
@@ -67,7 +96,16 @@ where
             sort: S::default(),
             range: R::default(),
             rate_limiter,
-            force_limit: None,
+            rate_limit_buckets: RateLimitBuckets::default(),
+            retry_policy: RetryPolicy::default(),
+            idempotency_header: IDEMPOTENCY_KEY,
+            client: None,
+            concurrency: None,
+            circuit_breaker: None,
+            error_decoder: None,
+            token_source: None,
+            signer: None,
+            digest: None,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -114,9 +152,63 @@ where
         self
     }
 
-    /// Set the number of retry attempts on 429 responses
-    pub fn force_limit(mut self, limit: Option<u8>) -> Self {
-        self.force_limit = limit;
+    /// Set the rate limit buckets updated when the server names a quota
+    pub fn rate_limit_buckets(mut self, buckets: RateLimitBuckets) -> Self {
+        self.rate_limit_buckets = buckets;
+        self
+    }
+
+    /// Set the policy to retry the failed attempts of the request
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Set the header carrying the idempotency key of the retried POST and PATCH requests
+    pub fn idempotency_header(mut self, name: HeaderName) -> Self {
+        self.idempotency_header = name;
+        self
+    }
+
+    /// Set the HTTP client used to send the request
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Set the semaphore limiting the requests in flight
+    pub fn concurrency(mut self, semaphore: Option<Arc<Semaphore>>) -> Self {
+        self.concurrency = semaphore;
+        self
+    }
+
+    /// Set the circuit breaker the request goes through
+    pub fn circuit_breaker(mut self, breaker: Option<CircuitBreaker>) -> Self {
+        self.circuit_breaker = breaker;
+        self
+    }
+
+    /// Set the decoder of the error responses
+    pub fn error_decoder(mut self, decoder: Option<Arc<dyn ErrorDecoder>>) -> Self {
+        self.error_decoder = decoder;
+        self
+    }
+
+    /// Set the source of the access token sent with every attempt
+    pub fn token_source(mut self, source: Option<Arc<TokenSource>>) -> Self {
+        self.token_source = source;
+        self
+    }
+
+    /// Set the signer of every attempt
+    pub fn signer(mut self, signer: Option<Arc<dyn RequestSigner>>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Set the Digest credentials answering the challenges of the server
+    pub fn digest(mut self, digest: Option<Arc<DigestAuth>>) -> Self {
+        self.digest = digest;
         self
     }
 
@@ -131,7 +223,17 @@ where
             sort: self.sort,
             range: self.range,
             rate_limiter: self.rate_limiter,
-            force_limit: self.force_limit,
+            rate_limit_buckets: self.rate_limit_buckets,
+            retry_policy: self.retry_policy,
+            idempotency_header: self.idempotency_header,
+            idempotency_key: None,
+            client: self.client.unwrap_or_default(),
+            concurrency: self.concurrency,
+            circuit_breaker: self.circuit_breaker,
+            error_decoder: self.error_decoder,
+            token_source: self.token_source,
+            signer: self.signer,
+            digest: self.digest,
             _phantom: self._phantom,
         }
     }
@@ -0,0 +1,327 @@
+use std::fmt::Debug;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue};
+use sha2::{Sha256, Sha512};
+
+use crate::error::{ApiError, Result};
+
+/// Signer of the requests of an API
+///
+/// The signer sees the final request of every attempt and every page,
+/// with its query parameters, headers and body, right before it is sent.
+pub trait RequestSigner: Debug + Send + Sync {
+    /// Sign the request, adding the signature to its headers or its query
+    fn sign(&self, request: &mut reqwest::Request) -> Result<()>;
+}
+
+/// Location of a value added to the request
+///
+/// # Variants
+/// * Header - Header with the given name
+/// * Query - Query parameter with the given name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placement {
+    Header(String),
+    Query(String),
+}
+
+/// Format of the timestamp of the signed requests
+///
+/// # Variants
+/// * Seconds - Seconds since the Unix epoch
+/// * Milliseconds - Milliseconds since the Unix epoch
+/// * Rfc3339 - RFC 3339 date with milliseconds, such as `2020-12-08T09:08:57.715Z`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    Seconds,
+    #[default]
+    Milliseconds,
+    Rfc3339,
+}
+
+/// Part of the request in the signed string
+///
+/// # Variants
+/// * Timestamp - Timestamp of the request
+/// * Nonce - Nonce of the request, empty when the signer has no nonce
+/// * Method - HTTP method, such as `GET`
+/// * Path - Path of the URL, such as `/api/v3/order`
+/// * Query - Query of the URL, without `?`
+/// * PathAndQuery - Path of the URL, followed by `?` and the query when there is one
+/// * Body - Raw body of the request, empty when there is none
+/// * Text - Fixed text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignedPart {
+    Timestamp,
+    Nonce,
+    Method,
+    Path,
+    Query,
+    PathAndQuery,
+    Body,
+    Text(String),
+}
+
+/// Hash function of the HMAC
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+/// Encoding of the signature
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// HMAC signer of vendor specific signature schemes
+///
+/// For every attempt, the signer adds the key, the timestamp and the nonce to the request,
+/// signs the parts of the request joined by the separator, and adds the signature.\
+/// The parameters added to the query are part of the signed `Query`.
+///
+/// # Attributes
+/// * secret - Secret of the HMAC
+/// * algorithm - Hash function of the HMAC (Default = Sha256)
+/// * encoding - Encoding of the signature (Default = Hex)
+/// * signature - Location of the signature (Default = `X-Signature` header)
+/// * timestamp - Location of the timestamp (Default = `X-Timestamp` header)
+/// * timestamp_format - Format of the timestamp (Default = Milliseconds)
+/// * nonce - Location of a random nonce (Default = None)
+/// * key - Location and value of the API key (Default = None)
+/// * parts - Parts of the signed string (Default = Timestamp, Method, Path, Query, Body)
+/// * separator - Separator of the parts (Default = empty)
+///
+/// # Example
+/// ```rust,ignore
+/// let signer = HmacSigner::new("secret")
+///     .key(Placement::Header(String::from("X-MBX-APIKEY")), "api_key")
+///     .timestamp(Placement::Query(String::from("timestamp")), TimestampFormat::Milliseconds)
+///     .signature(Placement::Query(String::from("signature")))
+///     .parts(vec![SignedPart::Query, SignedPart::Body]);
+/// let api: Api = ApiBuilder::new("https://api.binance.com").signer(signer).build();
+/// ```
+#[derive(Clone)]
+pub struct HmacSigner {
+    pub(crate) secret: Vec<u8>,
+    pub(crate) algorithm: HmacAlgorithm,
+    pub(crate) encoding: SignatureEncoding,
+    pub(crate) signature: Placement,
+    pub(crate) timestamp: Placement,
+    pub(crate) timestamp_format: TimestampFormat,
+    pub(crate) nonce: Option<Placement>,
+    pub(crate) key: Option<(Placement, String)>,
+    pub(crate) parts: Vec<SignedPart>,
+    pub(crate) separator: String,
+}
+
+impl HmacSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            algorithm: HmacAlgorithm::default(),
+            encoding: SignatureEncoding::default(),
+            signature: Placement::Header(String::from("X-Signature")),
+            timestamp: Placement::Header(String::from("X-Timestamp")),
+            timestamp_format: TimestampFormat::default(),
+            nonce: None,
+            key: None,
+            parts: vec![
+                SignedPart::Timestamp,
+                SignedPart::Method,
+                SignedPart::Path,
+                SignedPart::Query,
+                SignedPart::Body,
+            ],
+            separator: String::new(),
+        }
+    }
+
+    /// Create a signer from a base64 encoded secret
+    pub fn from_base64_secret(secret: impl AsRef<[u8]>) -> Result<Self> {
+        let secret = STANDARD
+            .decode(secret)
+            .map_err(|e| ApiError::RequestSigning(format!("Invalid base64 secret: {e}")))?;
+        Ok(Self::new(secret))
+    }
+
+    pub fn algorithm(mut self, algorithm: HmacAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: SignatureEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn signature(mut self, placement: Placement) -> Self {
+        self.signature = placement;
+        self
+    }
+
+    pub fn timestamp(mut self, placement: Placement, format: TimestampFormat) -> Self {
+        self.timestamp = placement;
+        self.timestamp_format = format;
+        self
+    }
+
+    pub fn nonce(mut self, placement: Placement) -> Self {
+        self.nonce = Some(placement);
+        self
+    }
+
+    pub fn key(mut self, placement: Placement, key: impl ToString) -> Self {
+        self.key = Some((placement, key.to_string()));
+        self
+    }
+
+    pub fn parts(mut self, parts: Vec<SignedPart>) -> Self {
+        self.parts = parts;
+        self
+    }
+
+    pub fn separator(mut self, separator: impl ToString) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Sign the request at the given time
+    ///
+    /// Returns `ApiError::RequestSigning` if the body is a stream and the body is signed
+    pub fn sign_at(&self, request: &mut reqwest::Request, time: DateTime<Utc>) -> Result<()> {
+        let timestamp = match self.timestamp_format {
+            TimestampFormat::Seconds => time.timestamp().to_string(),
+            TimestampFormat::Milliseconds => time.timestamp_millis().to_string(),
+            TimestampFormat::Rfc3339 => time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        };
+        let nonce = self
+            .nonce
+            .as_ref()
+            .map(|_| uuid::Uuid::new_v4().simple().to_string());
+
+        if let Some((placement, key)) = &self.key {
+            place(request, placement, key)?;
+        }
+        place(request, &self.timestamp, &timestamp)?;
+        if let (Some(placement), Some(nonce)) = (&self.nonce, &nonce) {
+            place(request, placement, nonce)?;
+        }
+
+        let mut message = Vec::new();
+        for (index, part) in self.parts.iter().enumerate() {
+            if index > 0 {
+                message.extend_from_slice(self.separator.as_bytes());
+            }
+            let url = request.url();
+            match part {
+                SignedPart::Timestamp => message.extend_from_slice(timestamp.as_bytes()),
+                SignedPart::Nonce => {
+                    message.extend_from_slice(nonce.as_deref().unwrap_or_default().as_bytes())
+                }
+                SignedPart::Method => {
+                    message.extend_from_slice(request.method().as_str().as_bytes())
+                }
+                SignedPart::Path => message.extend_from_slice(url.path().as_bytes()),
+                SignedPart::Query => {
+                    message.extend_from_slice(url.query().unwrap_or_default().as_bytes())
+                }
+                SignedPart::PathAndQuery => {
+                    message.extend_from_slice(url.path().as_bytes());
+                    if let Some(query) = url.query() {
+                        message.push(b'?');
+                        message.extend_from_slice(query.as_bytes());
+                    }
+                }
+                SignedPart::Body => match request.body().map(|body| body.as_bytes()) {
+                    None => {}
+                    Some(Some(bytes)) => message.extend_from_slice(bytes),
+                    Some(None) => {
+                        return Err(ApiError::RequestSigning(String::from(
+                            "A streamed body cannot be signed",
+                        )));
+                    }
+                },
+                SignedPart::Text(text) => message.extend_from_slice(text.as_bytes()),
+            }
+        }
+
+        let signature = self.mac(&message)?;
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => signature.iter().map(|byte| format!("{byte:02x}")).collect(),
+            SignatureEncoding::Base64 => STANDARD.encode(signature),
+        };
+        place(request, &self.signature, &signature)
+    }
+
+    fn mac(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let invalid_key = |e: hmac::digest::InvalidLength| ApiError::RequestSigning(e.to_string());
+        Ok(match self.algorithm {
+            HmacAlgorithm::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).map_err(invalid_key)?;
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+            HmacAlgorithm::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(&self.secret).map_err(invalid_key)?;
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+        })
+    }
+}
+
+impl RequestSigner for HmacSigner {
+    fn sign(&self, request: &mut reqwest::Request) -> Result<()> {
+        self.sign_at(request, Utc::now())
+    }
+}
+
+/// The secret and the API key are never printed
+impl Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("algorithm", &self.algorithm)
+            .field("encoding", &self.encoding)
+            .field("signature", &self.signature)
+            .field("timestamp", &self.timestamp)
+            .field("timestamp_format", &self.timestamp_format)
+            .field("nonce", &self.nonce)
+            .field("parts", &self.parts)
+            .field("separator", &self.separator)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Add the value to the request, replacing the header or the query parameter of the same name
+fn place(request: &mut reqwest::Request, placement: &Placement, value: &str) -> Result<()> {
+    match placement {
+        Placement::Header(name) => {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ApiError::RequestSigning(format!("Invalid header {name}: {e}")))?;
+            request
+                .headers_mut()
+                .insert(name, HeaderValue::from_str(value)?);
+        }
+        Placement::Query(name) => {
+            let url = request.url_mut();
+            if url.query_pairs().any(|(key, _)| key == name.as_str()) {
+                let pairs: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(key, _)| key != name.as_str())
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect();
+                url.query_pairs_mut().clear().extend_pairs(pairs);
+            }
+            url.query_pairs_mut().append_pair(name, value);
+        }
+    }
+    Ok(())
+}
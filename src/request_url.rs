@@ -16,12 +16,14 @@ use crate::{
 /// * route - Route to be used in the request
 /// * query - Query to be used in the request
 /// * method - HTTP method to be used in the request
-#[derive(Debug, Clone)]
+/// * api_key - Name and value of the API key query parameter, added to every page
+#[derive(Clone)]
 pub struct RequestUrl {
     pub(crate) endpoint: String,
     pub(crate) route: String,
     pub(crate) query: Query,
     pub(crate) method: Method,
+    pub(crate) api_key: Option<(String, String)>,
 }
 
 impl RequestUrl {
@@ -31,6 +33,7 @@ impl RequestUrl {
             route: String::new(),
             query: Query::new(),
             method: Method::GET,
+            api_key: None,
         }
     }
 
@@ -58,8 +61,22 @@ impl RequestUrl {
         self
     }
 
+    /// Send the API key in the query parameter `name`
+    pub fn api_key(mut self, name: impl ToString, key: impl ToString) -> Self {
+        self.api_key = Some((name.to_string(), key.to_string()));
+        self
+    }
+
+    /// Format a URL of the request without its secrets, including the API key query parameter
+    pub fn redact(&self, url: &Url) -> String {
+        match &self.api_key {
+            Some((name, _)) => redact_url_with(url, &[name]),
+            None => redact_url(url),
+        }
+    }
+
     /// Convert the request URL to a URL
-    /// that can be used in a request (Contains the query with pagination)
+    /// that can be used in a request (Contains the query with pagination and the API key)
     pub fn as_url<P: Pagination, F: Filter, S: Sort, R: Range>(
         &self,
         pagination: &P,
@@ -76,8 +93,72 @@ impl RequestUrl {
         query = query.join(filter.into());
         query = query.join(sort.into());
         query = query.join(range.into());
+        if let Some((name, key)) = &self.api_key {
+            let key: String = url::form_urlencoded::byte_serialize(key.as_bytes()).collect();
+            query = query.join(Query::from(name, key));
+        }
 
         Url::parse(&format!("{}{}{query}", self.endpoint, self.route))
             .map_err(ApiError::WrongUrlFormat)
     }
 }
+
+/// The API key is never printed
+impl std::fmt::Debug for RequestUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestUrl")
+            .field("endpoint", &self.endpoint)
+            .field("route", &self.route)
+            .field("query", &self.query)
+            .field("method", &self.method)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Query parameters whose value is hidden by [redact_url]
+const SECRET_PARAMETERS: [&str; 12] = [
+    "access_token",
+    "api_key",
+    "apikey",
+    "client_secret",
+    "code",
+    "key",
+    "password",
+    "refresh_token",
+    "secret",
+    "signature",
+    "sig",
+    "token",
+];
+
+/// Format a URL without its secrets, to be logged or returned in an error
+///
+/// The password of the URL and the values of the secret query parameters are replaced by `REDACTED`
+pub fn redact_url(url: &Url) -> String {
+    redact_url_with(url, &[])
+}
+
+/// Format a URL without its secrets, hiding the given query parameters as well
+pub fn redact_url_with(url: &Url, secrets: &[&str]) -> String {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("REDACTED"));
+    }
+    let is_secret = |key: &str| {
+        SECRET_PARAMETERS
+            .iter()
+            .chain(secrets)
+            .any(|secret| key.eq_ignore_ascii_case(secret))
+    };
+    if url.query_pairs().any(|(key, _)| is_secret(&key)) {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(key, value)| match is_secret(&key) {
+                true => (key.into_owned(), String::from("REDACTED")),
+                false => (key.into_owned(), value.into_owned()),
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
@@ -0,0 +1,276 @@
+use std::time::Duration;
+
+use reqwest::{
+    Method, StatusCode,
+    header::{HeaderMap, HeaderName},
+};
+
+use crate::rate_limiter::RateLimitHeaders;
+
+/// Default header carrying the idempotency key of the retried POST and PATCH requests
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Kind of transport error that can be retried
+///
+/// # Variants
+/// * Connect - The connection to the server failed
+/// * Timeout - The request or the connection timed out
+/// * Request - The request failed while being sent, such as a connection reset
+/// * Body - The body of the request or the response failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryErrorKind {
+    Connect,
+    Timeout,
+    Request,
+    Body,
+}
+
+impl RetryErrorKind {
+    /// Check if the error is of this kind
+    pub fn matches(&self, error: &reqwest::Error) -> bool {
+        match self {
+            Self::Connect => error.is_connect(),
+            Self::Timeout => error.is_timeout(),
+            Self::Request => error.is_request(),
+            Self::Body => error.is_body(),
+        }
+    }
+}
+
+/// Policy to retry the failed attempts of a request
+///
+/// The delay between two attempts grows exponentially from `base_backoff` up to `max_backoff`.\
+/// With jitter, the delay is picked randomly between half and the whole backoff,
+/// so concurrent clients do not retry at the same time.\
+/// A `Retry-After` header on a retryable response replaces the backoff,
+/// and the request is not retried if the server asks to wait longer than `max_backoff`.\
+/// Only idempotent methods are retried, unless `retry_non_idempotent` is enabled:
+/// POST and PATCH requests are then sent with an idempotency key, the same for every attempt.
+///
+/// # Attributes
+/// * max_attempts - Maximum number of attempts, including the first one
+/// * base_backoff - Delay before the first retry
+/// * max_backoff - Maximum delay between two attempts
+/// * jitter - Randomize the delay between two attempts
+/// * statuses - Response status codes to retry
+/// * error_kinds - Transport errors to retry
+/// * respect_retry_after - Wait for the `Retry-After` header of the response
+/// * retry_non_idempotent - Retry the POST and PATCH requests too
+///
+/// # Example
+/// ```rust,ignore
+/// let policy = RetryPolicy::new(5)
+///     .base_backoff(Duration::from_millis(200))
+///     .max_backoff(Duration::from_secs(10));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) base_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) jitter: bool,
+    pub(crate) statuses: Vec<StatusCode>,
+    pub(crate) error_kinds: Vec<RetryErrorKind>,
+    pub(crate) respect_retry_after: bool,
+    pub(crate) retry_non_idempotent: bool,
+}
+
+/// No retry
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy allowing `max_attempts` attempts
+    ///
+    /// # Default
+    /// * base_backoff - 500ms
+    /// * max_backoff - 30s
+    /// * jitter - true
+    /// * statuses - 429, 502, 503, 504
+    /// * error_kinds - Connect, Timeout, Request
+    /// * respect_retry_after - true
+    /// * retry_non_idempotent - false
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            error_kinds: vec![
+                RetryErrorKind::Connect,
+                RetryErrorKind::Timeout,
+                RetryErrorKind::Request,
+            ],
+            respect_retry_after: true,
+            retry_non_idempotent: false,
+        }
+    }
+
+    /// Policy without any retry
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Policy of the deprecated `force_limit`: `limit` retries of the 429 responses
+    pub(crate) fn force_limit(limit: Option<u8>) -> Self {
+        match limit {
+            Some(limit) => {
+                Self::new(u32::from(limit) + 1).statuses([StatusCode::TOO_MANY_REQUESTS])
+            }
+            None => Self::none(),
+        }
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn base_backoff(mut self, backoff: Duration) -> Self {
+        self.base_backoff = backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Replace the response status codes to retry
+    pub fn statuses(mut self, statuses: impl IntoIterator<Item = StatusCode>) -> Self {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Replace the transport errors to retry
+    pub fn error_kinds(mut self, kinds: impl IntoIterator<Item = RetryErrorKind>) -> Self {
+        self.error_kinds = kinds.into_iter().collect();
+        self
+    }
+
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    /// Retry the POST and PATCH requests too
+    ///
+    /// These requests are sent with an idempotency key,
+    /// so the server can detect the attempts of a request it already processed
+    pub fn retry_non_idempotent(mut self, retry: bool) -> Self {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    pub fn get_max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Check if a request with this method can be retried
+    pub fn is_retryable_method(&self, method: &Method) -> bool {
+        is_idempotent(method) || self.retry_non_idempotent
+    }
+
+    /// Check if the requests with this method need an idempotency key
+    ///
+    /// A key is needed when a non idempotent request can be sent more than once
+    pub fn needs_idempotency_key(&self, method: &Method) -> bool {
+        !is_idempotent(method) && self.retry_non_idempotent && self.max_attempts > 1
+    }
+
+    /// Check if a response with this status should be retried
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.statuses.contains(&status)
+    }
+
+    /// Check if a transport error should be retried
+    pub fn is_retryable_error(&self, error: &reqwest::Error) -> bool {
+        self.error_kinds.iter().any(|kind| kind.matches(error))
+    }
+
+    /// Exponential backoff before the retry following the attempt number `attempt` (starting at 1)
+    ///
+    /// Without jitter, the backoff is `base_backoff * 2^(attempt - 1)`, capped to `max_backoff`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self
+            .base_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        if !self.jitter {
+            return backoff;
+        }
+        let half = backoff / 2;
+        let spread = (backoff - half).as_millis() as u64;
+        half + Duration::from_millis(rand::random_range(0..=spread))
+    }
+
+    /// Delay before retrying a response, or `None` if it must not be retried
+    pub(crate) fn delay_for_status(
+        &self,
+        method: &Method,
+        status: StatusCode,
+        headers: &HeaderMap,
+        attempt: u32,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts
+            || !self.is_retryable_method(method)
+            || !self.is_retryable_status(status)
+        {
+            return None;
+        }
+        if !self.respect_retry_after {
+            return Some(self.backoff(attempt));
+        }
+        match RateLimitHeaders::from_headers(headers).retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => {
+                log::error!(
+                    "Server asked to retry after {retry_after:?}, more than the maximum backoff {:?}",
+                    self.max_backoff
+                );
+                None
+            }
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Delay before retrying a transport error, or `None` if it must not be retried
+    pub(crate) fn delay_for_error(
+        &self,
+        method: &Method,
+        error: &reqwest::Error,
+        attempt: u32,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts
+            || !self.is_retryable_method(method)
+            || !self.is_retryable_error(error)
+        {
+            return None;
+        }
+        Some(self.backoff(attempt))
+    }
+}
+
+/// Idempotent methods can be sent several times with the same effect (RFC 9110)
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
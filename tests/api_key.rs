@@ -0,0 +1,145 @@
+use httpmock::MockServer;
+
+#[cfg(test)]
+mod api_key_tests {
+    use reqt::{prelude::*, request_url::redact_url_with};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[derive(Debug, Clone, ApiKey)]
+    #[api_key_header("Ocp-Apim-Subscription-Key")]
+    struct TestApiKeyHeaderConnector {
+        key: String,
+    }
+
+    #[derive(Debug, Clone, ApiKey)]
+    struct TestApiKeyDefaultConnector {
+        key: String,
+    }
+
+    #[tokio::test]
+    async fn default_header() -> Result<()> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .header("x-api-key", "1234567890abcdef");
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        let api: Api = TestApiKeyDefaultConnector {
+            key: String::from("1234567890abcdef"),
+        }
+        .connect(&server.base_url())
+        .await?;
+
+        api.get::<Value>("/status")?.await?;
+        mock.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn custom_header() -> Result<()> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .header("ocp-apim-subscription-key", "1234567890abcdef")
+                .header_missing("x-api-key");
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        let api: Api = TestApiKeyHeaderConnector {
+            key: String::from("1234567890abcdef"),
+        }
+        .connect(&server.base_url())
+        .await?;
+
+        api.get::<Value>("/status")?.await?;
+        mock.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn query_parameter_is_kept_on_every_page() -> Result<()> {
+        let server = MockServer::start();
+        let pages = server.mock(|when, then| {
+            when.method("GET")
+                .path("/listings")
+                .query_param_exists("page[number]")
+                .query_param("apikey", "12345 67890&abcdef")
+                .header_missing("x-api-key");
+            then.status(200)
+                .header("X-Total", "300")
+                .header("X-Per-Page", "100")
+                .json_body(json!([{"id": 1}]));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .api_key_in(
+                "12345 67890&abcdef",
+                ApiKeyLocation::Query(String::from("apikey")),
+            )
+            .build()
+            .pagination(PaginationRule::Fixed(3));
+
+        let listings: Vec<Value> = api.get("/listings")?.await?;
+        assert_eq!(listings.len(), 3);
+        pages.assert_calls(3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cookie() -> Result<()> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .cookie("session", "1234567890abcdef")
+                .header_missing("x-api-key");
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .api_key_in(
+                "1234567890abcdef",
+                ApiKeyLocation::Cookie(String::from("session")),
+            )
+            .build();
+
+        api.get::<Value>("/status")?.await?;
+        mock.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn query_parameter_is_redacted_from_errors() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/status");
+            then.status(404);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .api_key_in(
+                "1234567890abcdef",
+                ApiKeyLocation::Query(String::from("subscription-key")),
+            )
+            .build();
+
+        let Err(ApiError::Http(error)) = api.get::<Value>("/status")?.await else {
+            panic!("The request should fail with an HTTP error");
+        };
+        assert!(error.url.contains("subscription-key=REDACTED"));
+        assert!(!error.url.contains("1234567890abcdef"));
+        Ok(())
+    }
+
+    #[test]
+    fn redact_extra_parameters() {
+        let url = url::Url::parse("https://api.example.com/items?appid=secret&page=2").unwrap();
+        assert_eq!(
+            redact_url_with(&url, &["appid"]),
+            "https://api.example.com/items?appid=REDACTED&page=2"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use httpmock::MockServer;
+use reqwest::Url;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod authorization_code_tests {
+    use reqt::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+
+    fn flow(server: &MockServer) -> AuthorizationCodeFlow {
+        let client = OAuth2Client::new(server.url("/token"), "cli").scopes(["openid", "read"]);
+        AuthorizationCodeFlow::new(client, server.url("/authorize"))
+    }
+
+    /// Act as the browser: follow the redirect of the authorization server with `params`
+    fn redirect(url: &Url, params: impl Fn(&str) -> Vec<(&'static str, String)> + Send + 'static) {
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        let redirect_uri = query["redirect_uri"].clone();
+        let state = query["state"].clone();
+        tokio::spawn(async move {
+            let favicon = Url::parse(&redirect_uri)
+                .unwrap()
+                .join("/favicon.ico")
+                .unwrap();
+            let _ = reqwest::get(favicon).await;
+            let mut redirect = Url::parse(&redirect_uri).unwrap();
+            redirect.query_pairs_mut().extend_pairs(params(&state));
+            let _ = reqwest::get(redirect).await;
+        });
+    }
+
+    #[test]
+    fn pkce_matches_rfc_example() {
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+        assert_eq!(
+            pkce.challenge,
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(Pkce::new().verifier.len(), 43);
+    }
+
+    #[tokio::test]
+    async fn authorization_code_flow_connects() -> Result<()> {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "authorization_code")
+                .form_urlencoded_tuple("code", "the-code")
+                .form_urlencoded_tuple("client_id", "cli")
+                .form_urlencoded_tuple_exists("code_verifier")
+                .form_urlencoded_tuple_exists("redirect_uri");
+            then.status(200).json_body(json!({
+                "access_token": "user-token",
+                "expires_in": 3600,
+                "refresh_token": "refresh"
+            }));
+        });
+        let users = server.mock(|when, then| {
+            when.method("GET")
+                .path("/me")
+                .header("authorization", "Bearer user-token");
+            then.status(200).json_body(json!({"name": "user"}));
+        });
+
+        let mut authorize_query = HashMap::new();
+        let api: Api = flow(&server)
+            .param("prompt", "consent")
+            .connect(server.base_url(), |url| {
+                authorize_query = url.query_pairs().into_owned().collect();
+                redirect(url, |state| {
+                    vec![("code", "the-code".into()), ("state", state.into())]
+                });
+            })
+            .await?;
+
+        assert_eq!(authorize_query["response_type"], "code");
+        assert_eq!(authorize_query["client_id"], "cli");
+        assert_eq!(authorize_query["scope"], "openid read");
+        assert_eq!(authorize_query["code_challenge_method"], "S256");
+        assert_eq!(authorize_query["prompt"], "consent");
+        assert!(authorize_query["redirect_uri"].starts_with("http://127.0.0.1:"));
+        let user = api.get::<serde_json::Value>("/me")?.await?;
+        assert_eq!(user, json!({"name": "user"}));
+        assert_eq!(
+            api.token_source().unwrap().refresh_token().await.as_deref(),
+            Some("refresh")
+        );
+        token.assert_calls(1);
+        users.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn state_mismatch_is_rejected() {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(200).json_body(json!({"access_token": "token"}));
+        });
+
+        let result = flow(&server)
+            .authorize(|url| {
+                redirect(url, |_| {
+                    vec![("code", "the-code".into()), ("state", "forged".into())]
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(ApiError::OAuth2Flow(_))));
+        token.assert_calls(0);
+    }
+
+    #[tokio::test]
+    async fn denied_authorization_is_reported() {
+        let server = MockServer::start();
+
+        let result = flow(&server)
+            .authorize(|url| {
+                redirect(url, |state| {
+                    vec![("error", "access_denied".into()), ("state", state.into())]
+                })
+            })
+            .await;
+        let Err(ApiError::OAuth2Flow(message)) = result else {
+            panic!("Expected an OAuth2 flow error");
+        };
+        assert!(message.contains("access_denied"));
+    }
+
+    #[tokio::test]
+    async fn redirect_timeout() {
+        let server = MockServer::start();
+
+        let result = flow(&server)
+            .timeout(std::time::Duration::from_millis(50))
+            .authorize(|_| {})
+            .await;
+        assert!(matches!(result, Err(ApiError::OAuth2Flow(_))));
+    }
+
+    #[tokio::test]
+    async fn idle_and_malformed_connections_are_skipped() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("code", "the-code");
+            then.status(200)
+                .json_body(json!({"access_token": "user-token", "expires_in": 3600}));
+        });
+
+        let source = flow(&server)
+            .authorize(|url| {
+                let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+                let mut redirect = Url::parse(&query["redirect_uri"]).unwrap();
+                redirect
+                    .query_pairs_mut()
+                    .append_pair("code", "the-code")
+                    .append_pair("state", &query["state"]);
+                let address = format!("127.0.0.1:{}", redirect.port().unwrap());
+                tokio::spawn(async move {
+                    // A preconnected socket of the browser, never sending a request
+                    let _idle = tokio::net::TcpStream::connect(&address).await.unwrap();
+                    let mut malformed = tokio::net::TcpStream::connect(&address).await.unwrap();
+                    tokio::io::AsyncWriteExt::write_all(
+                        &mut malformed,
+                        b"GET http://[malformed HTTP/1.1\r\n\r\n",
+                    )
+                    .await
+                    .unwrap();
+                    let _ = reqwest::get(redirect).await;
+                    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                });
+            })
+            .await?;
+
+        assert_eq!(source.token().await?, "user-token");
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use httpmock::MockServer;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(test)]
+mod circuit_breaker_tests {
+    use reqt::prelude::*;
+
+    use super::*;
+
+    type Changes = Arc<Mutex<Vec<(CircuitState, CircuitState)>>>;
+
+    fn breaker(cool_down: Duration) -> (CircuitBreaker, Changes) {
+        let changes = Changes::default();
+        let recorded = changes.clone();
+        let breaker = CircuitBreaker::new(0.5, cool_down)
+            .minimum_requests(2)
+            .on_state_change(move |_, from, to| recorded.lock().unwrap().push((from, to)));
+        (breaker, changes)
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_and_fails_fast() -> Result<()> {
+        let server = MockServer::start();
+        let down = server.mock(|when, then| {
+            when.method("GET").path("/down");
+            then.status(503);
+        });
+        let (breaker, changes) = breaker(Duration::from_secs(60));
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .circuit_breaker(breaker)
+            .build();
+
+        for _ in 0..2 {
+            assert!(api.get::<()>("/down")?.await.is_err());
+        }
+        let result = api.get::<()>("/down")?.await;
+        assert!(matches!(result, Err(ApiError::CircuitOpen(_))));
+        down.assert_calls(2);
+
+        let host = server.address().to_string();
+        let state = api.circuit_breaker().map(|b| b.state(&host));
+        assert_eq!(state, Some(CircuitState::Open));
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![(CircuitState::Closed, CircuitState::Open)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn circuit_closes_after_successful_probe() -> Result<()> {
+        let server = MockServer::start();
+        let mut down = server.mock(|when, then| {
+            when.method("GET").path("/flaky");
+            then.status(500);
+        });
+        let (breaker, changes) = breaker(Duration::from_millis(200));
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .circuit_breaker(breaker.clone())
+            .build();
+
+        for _ in 0..2 {
+            assert!(api.get::<()>("/flaky")?.await.is_err());
+        }
+        down.delete();
+        server.mock(|when, then| {
+            when.method("GET").path("/flaky");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body("null");
+        });
+        let host = server.address().to_string();
+        assert_eq!(breaker.state(&host), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(breaker.state(&host), CircuitState::HalfOpen);
+        api.get::<()>("/flaky")?.await?;
+        assert_eq!(breaker.state(&host), CircuitState::Closed);
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                (CircuitState::Closed, CircuitState::Open),
+                (CircuitState::Open, CircuitState::HalfOpen),
+                (CircuitState::HalfOpen, CircuitState::Closed),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn circuit_reopens_after_failed_probe() -> Result<()> {
+        let server = MockServer::start();
+        let down = server.mock(|when, then| {
+            when.method("GET").path("/down");
+            then.status(502);
+        });
+        let (breaker, changes) = breaker(Duration::from_millis(100));
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .circuit_breaker(breaker.clone())
+            .build();
+
+        for _ in 0..2 {
+            assert!(api.get::<()>("/down")?.await.is_err());
+        }
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(api.get::<()>("/down")?.await.is_err());
+        let result = api.get::<()>("/down")?.await;
+        assert!(matches!(result, Err(ApiError::CircuitOpen(_))));
+        down.assert_calls(3);
+        assert_eq!(
+            changes.lock().unwrap().last(),
+            Some(&(CircuitState::HalfOpen, CircuitState::Open))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_errors_do_not_open_the_circuit() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/missing");
+            then.status(404);
+        });
+        let (breaker, changes) = breaker(Duration::from_secs(60));
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .circuit_breaker(breaker)
+            .build();
+
+        for _ in 0..4 {
+            assert!(matches!(
+                api.get::<()>("/missing")?.await,
+                Err(ApiError::Http(error)) if error.status == reqwest::StatusCode::NOT_FOUND
+            ));
+        }
+        assert!(changes.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn zero_failure_rate_opens_on_a_failure_only() -> Result<()> {
+        let server = MockServer::start();
+        let mut up = server.mock(|when, then| {
+            when.method("GET").path("/flaky");
+            then.status(200).body("null");
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .circuit_breaker(CircuitBreaker::new(0., Duration::from_secs(60)).minimum_requests(2))
+            .build();
+        let host = server.address().to_string();
+        let state = |api: &Api| api.circuit_breaker().map(|b| b.state(&host));
+
+        for _ in 0..3 {
+            api.get::<()>("/flaky")?.await?;
+        }
+        assert_eq!(state(&api), Some(CircuitState::Closed));
+
+        up.delete();
+        server.mock(|when, then| {
+            when.method("GET").path("/flaky");
+            then.status(503);
+        });
+        assert!(api.get::<()>("/flaky")?.await.is_err());
+        assert_eq!(state(&api), Some(CircuitState::Open));
+        Ok(())
+    }
+}
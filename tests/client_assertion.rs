@@ -0,0 +1,179 @@
+#![cfg(feature = "private-key-jwt")]
+
+use httpmock::{HttpMockRequest, MockServer};
+
+#[cfg(test)]
+mod client_assertion_tests {
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+    use reqt::{OIDC, prelude::*};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    const RSA_KEY: &str = include_str!("fixtures/client_rsa.pem");
+    const RSA_PUBLIC_KEY: &str = include_str!("fixtures/client_rsa.pub.pem");
+    const EC_KEY: &str = include_str!("fixtures/client_ec.pem");
+    const EC_PUBLIC_KEY: &str = include_str!("fixtures/client_ec.pub.pem");
+    const EC_CERTIFICATE: &str = include_str!("fixtures/client_ec.crt.pem");
+
+    #[derive(Debug, Deserialize)]
+    struct Claims {
+        iss: String,
+        sub: String,
+        jti: String,
+    }
+
+    /// Check the client assertion of a token request, signed by the key for the audience
+    fn is_signed_assertion(
+        request: &HttpMockRequest,
+        key: DecodingKey,
+        algorithm: Algorithm,
+        audience: &str,
+    ) -> bool {
+        let Some((_, assertion)) = url::form_urlencoded::parse(request.body_ref())
+            .find(|(name, _)| name == "client_assertion")
+        else {
+            return false;
+        };
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[audience]);
+        jsonwebtoken::decode::<Claims>(&assertion, &key, &validation).is_ok_and(|token| {
+            token.claims.iss == "cli" && token.claims.sub == "cli" && !token.claims.jti.is_empty()
+        })
+    }
+
+    #[tokio::test]
+    async fn private_key_jwt_signs_an_assertion_per_request() -> Result<()> {
+        let server = MockServer::start();
+        let token_endpoint = server.url("/token");
+        let audience = token_endpoint.clone();
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .header_missing("authorization")
+                .form_urlencoded_tuple("client_id", "cli")
+                .form_urlencoded_tuple(
+                    "client_assertion_type",
+                    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+                )
+                .form_urlencoded_tuple_missing("client_secret")
+                .is_true(move |request| {
+                    let key = DecodingKey::from_rsa_pem(RSA_PUBLIC_KEY.as_bytes()).unwrap();
+                    is_signed_assertion(request, key, Algorithm::RS256, &audience)
+                });
+            then.status(200)
+                .json_body(json!({"access_token": "token", "expires_in": 3600}));
+        });
+        let key = ClientAssertionKey::from_pem(SigningAlgorithm::RS256, RSA_KEY)?.key_id("k1");
+        let client = OAuth2Client::new(&token_endpoint, "cli").private_key_jwt(key);
+
+        client.request_token(&Grant::ClientCredentials).await?;
+        client.request_token(&Grant::ClientCredentials).await?;
+        token.assert_calls(2);
+        Ok(())
+    }
+
+    #[test]
+    fn assertion_key_must_match_algorithm() {
+        let result = ClientAssertionKey::from_pem(SigningAlgorithm::ES256, RSA_KEY);
+        assert!(matches!(result, Err(ApiError::ClientAssertion(_))));
+        assert!(ClientAssertionKey::from_pem(SigningAlgorithm::ES256, EC_KEY).is_ok());
+    }
+
+    #[derive(Debug, Clone, Deserialize, Oauth2)]
+    #[auth_method(client_secret_basic)]
+    struct BasicConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        scopes: Vec<String>,
+        audience: String,
+        resource: String,
+    }
+
+    #[tokio::test]
+    async fn derive_sends_audience_and_resource_with_basic_auth() -> Result<()> {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .header("authorization", "Basic Y2xpOnNlY3JldA==")
+                .form_urlencoded_tuple_missing("client_secret")
+                .form_urlencoded_tuple("audience", "https://api.example.com")
+                .form_urlencoded_tuple("resource", "https://graph.example.com");
+            then.status(200)
+                .json_body(json!({"access_token": "token", "expires_in": 3600}));
+        });
+        let connector = BasicConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/token"),
+            scopes: Vec::new(),
+            audience: String::from("https://api.example.com"),
+            resource: String::from("https://graph.example.com"),
+        };
+
+        connector.connect(&server.base_url()).await?;
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Deserialize, OIDC)]
+    #[auth_method(private_key_jwt)]
+    #[signing_algorithm(ES256)]
+    struct PrivateKeyConnector {
+        client_id: String,
+        auth_endpoint: String,
+        scopes: Vec<String>,
+        private_key: String,
+        key_id: String,
+    }
+
+    #[tokio::test]
+    async fn derive_signs_assertion_with_ec_key() -> Result<()> {
+        let server = MockServer::start();
+        let audience = server.url("/token");
+        let token = server.mock(|when, then| {
+            when.method("POST").path("/token").is_true(move |request| {
+                let key = DecodingKey::from_ec_pem(EC_PUBLIC_KEY.as_bytes()).unwrap();
+                is_signed_assertion(request, key, Algorithm::ES256, &audience)
+            });
+            then.status(200)
+                .json_body(json!({"access_token": "token", "expires_in": 3600}));
+        });
+        let connector = PrivateKeyConnector {
+            client_id: String::from("cli"),
+            auth_endpoint: server.url("/token"),
+            scopes: Vec::new(),
+            private_key: String::from(EC_KEY),
+            key_id: String::from("k1"),
+        };
+
+        connector.connect(&server.base_url()).await?;
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tls_client_auth_only_sends_client_id() -> Result<()> {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .header_missing("authorization")
+                .form_urlencoded_tuple("client_id", "cli")
+                .form_urlencoded_tuple_missing("client_secret")
+                .form_urlencoded_tuple_missing("client_assertion");
+            then.status(200)
+                .json_body(json!({"access_token": "token", "expires_in": 3600}));
+        });
+        let client = OAuth2Client::new(server.url("/token"), "cli")
+            .client_secret("secret")
+            .tls_client_auth(EC_CERTIFICATE, EC_KEY)?;
+
+        client.request_token(&Grant::ClientCredentials).await?;
+        token.assert_calls(1);
+        Ok(())
+    }
+}
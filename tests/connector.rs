@@ -2,9 +2,7 @@
 mod connector_tests {
     use base64::{Engine, engine::general_purpose};
     use reqt::{Keycloak, prelude::*};
-    use reqwest::{Client, StatusCode};
     use serde::Deserialize;
-    use std::collections::HashMap;
 
     fn get_credentials_oauth2() -> TestApiOauth2Connector {
         TestApiOauth2Connector {
@@ -77,6 +75,14 @@ mod connector_tests {
         user_pass: String,
     }
 
+    #[derive(Debug, Clone, Deserialize, Oauth2)]
+    struct TestApiMockOauth2Connector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        scopes: Vec<String>,
+    }
+
     #[derive(Authorization)]
     struct TestApiNoAuthConnector {}
     impl TestApiNoAuthConnector {
@@ -275,4 +281,54 @@ mod connector_tests {
             .await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn malformed_token_response() {
+        let server = httpmock::MockServer::start();
+        server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(200).body("<html>maintenance</html>");
+        });
+        let connector = TestApiMockOauth2Connector {
+            client_id: String::from("id"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/token"),
+            scopes: vec![String::from("public")],
+        };
+        let result = connector.connect(&server.base_url()).await;
+        assert!(matches!(result, Err(ApiError::TokenResponseParse(_))));
+    }
+
+    #[tokio::test]
+    async fn oauth2_derive_sets_the_token() -> Result<()> {
+        let server = httpmock::MockServer::start();
+        server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(200).json_body(
+                serde_json::json!({"access_token": "derived-token", "expires_in": 3600}),
+            );
+        });
+        let connector = TestApiMockOauth2Connector {
+            client_id: String::from("id"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/token"),
+            scopes: vec![String::from("public")],
+        };
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer derived-token");
+        Ok(())
+    }
+
+    #[test]
+    fn none_authorization_does_not_panic() {
+        assert_eq!(AuthorizationType::None.to_string(), "");
+        assert!(matches!(
+            AuthorizationType::None.try_header(),
+            Err(ApiError::AuthorizationType)
+        ));
+        let keycloak = AuthorizationType::Keycloak(Box::new(AuthorizationType::None));
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert!(keycloak.header_value(&mut headers).is_ok());
+        assert!(headers.is_empty());
+    }
 }
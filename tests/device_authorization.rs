@@ -0,0 +1,182 @@
+use httpmock::MockServer;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+#[cfg(test)]
+mod device_authorization_tests {
+    use reqt::prelude::*;
+    use serde::Deserialize;
+    use serde_json::json;
+    use tokio::time::Instant;
+
+    use super::*;
+
+    fn flow(server: &MockServer) -> DeviceCodeFlow {
+        // Without the idle timer of the pool, the paused clock only advances with the polls
+        let http = reqwest::Client::builder()
+            .pool_idle_timeout(None)
+            .build()
+            .unwrap();
+        let client = OAuth2Client::new(server.url("/token"), "cli")
+            .scopes(["read"])
+            .http_client(http);
+        DeviceCodeFlow::new(client, server.url("/device"))
+    }
+
+    fn device_endpoint(server: &MockServer, expires_in: u64) {
+        server.mock(|when, then| {
+            when.method("POST")
+                .path("/device")
+                .form_urlencoded_tuple("client_id", "cli")
+                .form_urlencoded_tuple("scope", "read");
+            then.status(200).json_body(json!({
+                "device_code": "device-code",
+                "user_code": "WDJB-MJHT",
+                "verification_uri": "https://auth.example.com/device",
+                "expires_in": expires_in,
+                "interval": 1
+            }));
+        });
+    }
+
+    /// Answer `errors` to the first polls of the token endpoint, then the token
+    fn token_endpoint(server: &MockServer, errors: &'static [&'static str]) {
+        let polls = Arc::new(AtomicUsize::new(0));
+        server.mock(move |when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("device_code", "device-code")
+                .is_true(move |_| polls.fetch_add(1, Ordering::SeqCst) < errors.len());
+            then.status(400)
+                .json_body(json!({"error": "authorization_pending"}));
+        });
+        server.mock(|when, then| {
+            when.method("POST").path("/token").form_urlencoded_tuple(
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:device_code",
+            );
+            then.status(200)
+                .json_body(json!({"access_token": "device-token", "expires_in": 3600}));
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn device_flow_polls_until_approved() -> Result<()> {
+        let server = MockServer::start();
+        device_endpoint(&server, 600);
+        token_endpoint(&server, &["authorization_pending", "authorization_pending"]);
+        let users = server.mock(|when, then| {
+            when.method("GET")
+                .path("/me")
+                .header("authorization", "Bearer device-token");
+            then.status(200).json_body(json!({"name": "user"}));
+        });
+
+        let start = Instant::now();
+        let mut user_code = String::new();
+        let api: Api = flow(&server)
+            .connect(server.base_url(), |authorization| {
+                user_code = authorization.user_code.clone();
+            })
+            .await?;
+
+        assert_eq!(user_code, "WDJB-MJHT");
+        assert!(start.elapsed() >= Duration::from_secs(3));
+        assert!(start.elapsed() < Duration::from_secs(4));
+        let user = api.get::<serde_json::Value>("/me")?.await?;
+        assert_eq!(user, json!({"name": "user"}));
+        users.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_down_increases_interval() -> Result<()> {
+        let server = MockServer::start();
+        device_endpoint(&server, 600);
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        server.mock(move |when, then| {
+            when.method("POST")
+                .path("/token")
+                .is_true(move |_| counter.fetch_add(1, Ordering::SeqCst) < 1);
+            then.status(400).json_body(json!({"error": "slow_down"}));
+        });
+        token_endpoint(&server, &[]);
+
+        let start = Instant::now();
+        flow(&server).authorize(|_| {}).await?;
+        // 1s before the first poll, then 1s + 5s after the slow down
+        assert!(start.elapsed() >= Duration::from_secs(7));
+        assert!(start.elapsed() < Duration::from_secs(8));
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn device_code_expires() {
+        let server = MockServer::start();
+        device_endpoint(&server, 3);
+        server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(400)
+                .json_body(json!({"error": "authorization_pending"}));
+        });
+
+        let result = flow(&server).authorize(|_| {}).await;
+        let Err(ApiError::OAuth2Flow(message)) = result else {
+            panic!("Expected an OAuth2 flow error");
+        };
+        assert!(message.contains("expired"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn denied_device_is_reported() {
+        let server = MockServer::start();
+        device_endpoint(&server, 600);
+        server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(400)
+                .json_body(json!({"error": "access_denied"}));
+        });
+
+        let result = flow(&server).authorize(|_| {}).await;
+        assert!(matches!(result, Err(ApiError::OAuth2Flow(_))));
+    }
+
+    fn show_user_code(authorization: &DeviceAuthorization) {
+        assert_eq!(authorization.user_code, "WDJB-MJHT");
+    }
+
+    #[derive(Debug, Clone, Deserialize, Oauth2)]
+    #[grant(device_code)]
+    #[on_user_code(show_user_code)]
+    struct TestDeviceConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        device_endpoint: String,
+        scopes: Vec<String>,
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn derive_device_code_grant() -> Result<()> {
+        let server = MockServer::start();
+        device_endpoint(&server, 600);
+        token_endpoint(&server, &["authorization_pending"]);
+        let connector = TestDeviceConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/token"),
+            device_endpoint: server.url("/device"),
+            scopes: vec![String::from("read")],
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token_source().unwrap().token().await?, "device-token");
+        Ok(())
+    }
+}
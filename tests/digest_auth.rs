@@ -0,0 +1,198 @@
+use httpmock::{HttpMockRequest, MockServer};
+
+#[cfg(test)]
+mod digest_auth_tests {
+    use std::collections::HashMap;
+
+    use md5::{Digest, Md5};
+    use reqt::prelude::*;
+    use reqwest::{
+        Method,
+        header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE},
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    // Example of RFC 7616 section 3.9.1
+    const RFC_CHALLENGE: &str = r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=ALGORITHM, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+    const RFC_CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    fn rfc_authorization(algorithm: &str) -> Result<String> {
+        let digest = DigestAuth::new("Mufasa", "Circle of Life");
+        let challenge = DigestChallenge::parse(&RFC_CHALLENGE.replace("ALGORITHM", algorithm))
+            .unwrap()
+            .cnonce(RFC_CNONCE);
+        digest.set_challenge(challenge);
+        let url = url::Url::parse("http://www.example.org/dir/index.html")?;
+        let header = digest.authorization(&Method::GET, &url, b"")?.unwrap();
+        Ok(header.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn rfc_7616_md5() -> Result<()> {
+        assert_eq!(
+            rfc_authorization("MD5")?,
+            r#"Digest username="Mufasa", realm="http-auth@example.org", uri="/dir/index.html", algorithm=MD5, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", nc=00000001, cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", qop=auth, response="8ca523f5e9506fed4657c9700eebdbec", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rfc_7616_sha_256() -> Result<()> {
+        assert_eq!(
+            rfc_authorization("SHA-256")?,
+            r#"Digest username="Mufasa", realm="http-auth@example.org", uri="/dir/index.html", algorithm=SHA-256, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", nc=00000001, cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", qop=auth, response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn strongest_challenge_is_answered() {
+        let mut headers = HeaderMap::new();
+        for algorithm in ["MD5", "SHA-256", "SHA-512-256"] {
+            headers.append(
+                WWW_AUTHENTICATE,
+                HeaderValue::from_str(&RFC_CHALLENGE.replace("ALGORITHM", algorithm)).unwrap(),
+            );
+        }
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static("Basic realm=\"x\""),
+        );
+        let challenge = DigestChallenge::from_headers(&headers)
+            .unwrap()
+            .cnonce(RFC_CNONCE);
+        assert_eq!(
+            challenge,
+            DigestChallenge::parse(&RFC_CHALLENGE.replace("ALGORITHM", "SHA-256"))
+                .unwrap()
+                .cnonce(RFC_CNONCE),
+        );
+    }
+
+    /// Check the MD5 response of a request, for the user `admin` and the password `secret`
+    fn is_answered(request: &HttpMockRequest, nonce: &str, nonce_count: &str) -> bool {
+        let headers = request.headers();
+        let Some(header) = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Digest "))
+        else {
+            return false;
+        };
+        let params: HashMap<&str, &str> = header
+            .split(", ")
+            .filter_map(|param| param.split_once('='))
+            .map(|(name, value)| (name, value.trim_matches('"')))
+            .collect();
+        let hash = |data: String| format!("{:x}", Md5::digest(data));
+        let ha1 = hash(String::from("admin:camera:secret"));
+        let ha2 = hash(format!("{}:{}", request.method_str(), params["uri"]));
+        let response = hash(format!(
+            "{ha1}:{nonce}:{nonce_count}:{}:auth:{ha2}",
+            params["cnonce"]
+        ));
+        params["nonce"] == nonce && params["nc"] == nonce_count && params["response"] == response
+    }
+
+    fn challenge(nonce: &str, stale: bool) -> String {
+        format!(r#"Digest realm="camera", qop="auth", nonce="{nonce}", stale={stale}"#)
+    }
+
+    #[tokio::test]
+    async fn challenge_is_answered_and_nonce_count_tracked() -> Result<()> {
+        let server = MockServer::start();
+        let challenged = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .header_missing("authorization");
+            then.status(401)
+                .header("WWW-Authenticate", challenge("n1", false));
+        });
+        let first = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .is_true(|request| is_answered(request, "n1", "00000001"));
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        let second = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .is_true(|request| is_answered(request, "n1", "00000002"));
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .digest(DigestAuth::new("admin", "secret"))
+            .build();
+
+        api.get::<Value>("/status")?.await?;
+        api.get::<Value>("/status")?.await?;
+        challenged.assert_calls(1);
+        first.assert_calls(1);
+        second.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stale_nonce_is_challenged_again() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .header_missing("authorization");
+            then.status(401)
+                .header("WWW-Authenticate", challenge("n1", false));
+        });
+        let mut valid = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .header_includes("authorization", "nonce=\"n1\"");
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .digest(DigestAuth::new("admin", "secret"))
+            .build();
+        api.get::<Value>("/status")?.await?;
+
+        valid.delete();
+        let expired = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .header_includes("authorization", "nonce=\"n1\"");
+            then.status(401)
+                .header("WWW-Authenticate", challenge("n2", true));
+        });
+        let renewed = server.mock(|when, then| {
+            when.method("GET")
+                .path("/status")
+                .is_true(|request| is_answered(request, "n2", "00000001"));
+            then.status(200).json_body(json!({"status": "ok"}));
+        });
+        api.get::<Value>("/status")?.await?;
+        expired.assert_calls(1);
+        renewed.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_are_not_retried() -> Result<()> {
+        let server = MockServer::start();
+        let rejected = server.mock(|when, then| {
+            when.method("GET").path("/status");
+            then.status(401)
+                .header("WWW-Authenticate", challenge("n1", false));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .digest(DigestAuth::new("admin", "wrong"))
+            .build();
+
+        let result = api.get::<Value>("/status")?.await;
+        assert!(result.is_err());
+        rejected.assert_calls(2);
+        Ok(())
+    }
+}
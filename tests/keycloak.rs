@@ -0,0 +1,245 @@
+use httpmock::MockServer;
+
+#[cfg(test)]
+mod keycloak_tests {
+    use reqt::{Keycloak, prelude::*};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    fn realm(server: &MockServer, realm: &str) {
+        let issuer = server.url(format!("/realms/{realm}"));
+        server.mock(|when, then| {
+            when.method("GET")
+                .path(format!("/realms/{realm}/.well-known/openid-configuration"));
+            then.status(200).json_body(json!({
+                "issuer": issuer,
+                "token_endpoint": format!("{issuer}/protocol/openid-connect/token"),
+                "grant_types_supported": [
+                    "password",
+                    "client_credentials",
+                    "refresh_token",
+                    "urn:ietf:params:oauth:grant-type:token-exchange"
+                ],
+                "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"]
+            }));
+        });
+    }
+
+    #[derive(Debug, Clone, Deserialize, Keycloak)]
+    #[auth_type(OIDC)]
+    #[grant(client_credentials)]
+    struct ServiceConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        realm: String,
+    }
+
+    #[tokio::test]
+    async fn client_credentials_grant() -> Result<()> {
+        let server = MockServer::start();
+        realm(&server, "service");
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/service/protocol/openid-connect/token")
+                .header_exists("authorization")
+                .form_urlencoded_tuple("grant_type", "client_credentials");
+            then.status(200)
+                .json_body(json!({"access_token": "service-token", "expires_in": 300}));
+        });
+        let connector = ServiceConnector {
+            client_id: String::from("service"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("service"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer service-token");
+        assert_eq!(api.token_source().unwrap().token().await?, "service-token");
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Deserialize, Keycloak)]
+    #[auth_type(Bearer)]
+    #[offline_access]
+    struct PublicOfflineConnector {
+        client_id: String,
+        auth_endpoint: String,
+        realm: String,
+        user_login: String,
+        user_pass: String,
+        scopes: Vec<String>,
+    }
+
+    #[tokio::test]
+    async fn public_client_requests_offline_token() -> Result<()> {
+        let server = MockServer::start();
+        realm(&server, "public");
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/public/protocol/openid-connect/token")
+                .header_missing("authorization")
+                .form_urlencoded_tuple("client_id", "cli")
+                .form_urlencoded_tuple_missing("client_secret")
+                .form_urlencoded_tuple("grant_type", "password")
+                .form_urlencoded_tuple("scope", "openid offline_access");
+            then.status(200).json_body(json!({
+                "access_token": "user-token",
+                "refresh_token": "offline-token",
+                "expires_in": 300
+            }));
+        });
+        let connector = PublicOfflineConnector {
+            client_id: String::from("cli"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("public"),
+            user_login: String::from("user"),
+            user_pass: String::from("pass"),
+            scopes: vec![String::from("openid")],
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        let source = api.token_source().unwrap();
+        assert_eq!(
+            source.refresh_token().await.as_deref(),
+            Some("offline-token")
+        );
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Deserialize, Keycloak)]
+    #[auth_type(Bearer)]
+    #[grant(refresh_token)]
+    struct OfflineConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        realm: String,
+        refresh_token: String,
+    }
+
+    #[tokio::test]
+    async fn refresh_token_grant() -> Result<()> {
+        let server = MockServer::start();
+        realm(&server, "offline");
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/offline/protocol/openid-connect/token")
+                .form_urlencoded_tuple("grant_type", "refresh_token")
+                .form_urlencoded_tuple("refresh_token", "offline-token");
+            then.status(200)
+                .json_body(json!({"access_token": "refreshed-token", "expires_in": 300}));
+        });
+        let connector = OfflineConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("offline"),
+            refresh_token: String::from("offline-token"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer refreshed-token");
+        // The offline token is kept when the server does not rotate it
+        let source = api.token_source().unwrap();
+        assert_eq!(
+            source.refresh_token().await.as_deref(),
+            Some("offline-token")
+        );
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Deserialize, Keycloak)]
+    #[auth_type(OAuth2)]
+    #[grant(token_exchange)]
+    struct ImpersonationConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        realm: String,
+        subject_token: String,
+        audience: String,
+        requested_subject: String,
+    }
+
+    #[tokio::test]
+    async fn token_exchange_grant() -> Result<()> {
+        let server = MockServer::start();
+        realm(&server, "exchange");
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/exchange/protocol/openid-connect/token")
+                .form_urlencoded_tuple(
+                    "grant_type",
+                    "urn:ietf:params:oauth:grant-type:token-exchange",
+                )
+                .form_urlencoded_tuple("subject_token", "service-token")
+                .form_urlencoded_tuple(
+                    "subject_token_type",
+                    "urn:ietf:params:oauth:token-type:access_token",
+                )
+                .form_urlencoded_tuple("audience", "billing")
+                .form_urlencoded_tuple("requested_subject", "alice")
+                .form_urlencoded_tuple_missing("subject_issuer");
+            then.status(200)
+                .json_body(json!({"access_token": "alice-token", "expires_in": 300}));
+        });
+        let connector = ImpersonationConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("exchange"),
+            subject_token: String::from("service-token"),
+            audience: String::from("billing"),
+            requested_subject: String::from("alice"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer alice-token");
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Deserialize, Keycloak)]
+    #[auth_type(None)]
+    #[grant(client_credentials)]
+    struct AnonymousConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        realm: String,
+    }
+
+    #[tokio::test]
+    async fn none_auth_type_requests_no_token() -> Result<()> {
+        let server = MockServer::start();
+        let discovery = server.mock(|when, then| {
+            when.method("GET")
+                .path("/realms/anonymous/.well-known/openid-configuration");
+            then.status(500);
+        });
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/anonymous/protocol/openid-connect/token");
+            then.status(500);
+        });
+        let connector = AnonymousConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("anonymous"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert!(api.token_source().is_none());
+        discovery.assert_calls(0);
+        token.assert_calls(0);
+        Ok(())
+    }
+}
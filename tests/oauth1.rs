@@ -0,0 +1,156 @@
+use httpmock::MockServer;
+
+#[cfg(test)]
+mod oauth1_tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    use reqt::prelude::*;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    const RSA_KEY: &str = include_str!("fixtures/client_rsa.pem");
+    const RSA_PKCS1_KEY: &str = include_str!("fixtures/client_rsa_pkcs1.pem");
+
+    fn photos_request() -> reqwest::Request {
+        reqwest::Client::new()
+            .get("http://photos.example.net/photos?file=vacation.jpg&size=original")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn rfc_5849_hmac_sha1() -> Result<()> {
+        // RFC 5849 section 1.2, access to the protected resource
+        let oauth1 = OAuth1::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44")
+            .token("nnch734d00sl2jdk", "pfkkdhi9sl3r4s00")
+            .realm("Photos");
+        let mut request = photos_request();
+        oauth1.sign_with(&mut request, "chapoH", 137131202)?;
+        assert_eq!(
+            request.headers()["authorization"],
+            r#"OAuth realm="Photos", oauth_consumer_key="dpf43f3p2l4k3l03", oauth_token="nnch734d00sl2jdk", oauth_signature_method="HMAC-SHA1", oauth_timestamp="137131202", oauth_nonce="chapoH", oauth_signature="MdpQcU8iPSUjWoN%2FUDMsK2sui9I%3D""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rfc_5849_signature_base_string() -> Result<()> {
+        // RFC 5849 section 3.4.1.1, with a form body
+        let oauth1 = OAuth1::new("9djdj82h48djs9d2", "").token("kkk9d7dh3k39sjv7", "");
+        let request = reqwest::Client::new()
+            .post("http://example.com/request?b5=%3D%253D&a3=a&c%40=&a2=r%20b")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("c2&a3=2+q")
+            .build()
+            .unwrap();
+        assert_eq!(
+            oauth1.signature_base_string(&request, "7d8f3e4a", 137131201)?,
+            "POST&http%3A%2F%2Fexample.com%2Frequest&a2%3Dr%2520b%26a3%3D2%2520q%26a3%3Da%26b5%3D%253D%25253D%26c%2540%3D%26c2%3D%26oauth_consumer_key%3D9djdj82h48djs9d2%26oauth_nonce%3D7d8f3e4a%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D137131201%26oauth_token%3Dkkk9d7dh3k39sjv7"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rfc_5849_plaintext_temporary_credentials() -> Result<()> {
+        // RFC 5849 section 1.2, request of the temporary credentials
+        let oauth1 = OAuth1::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44")
+            .plaintext()
+            .realm("Photos")
+            .callback("http://printer.example.com/ready");
+        let mut request = reqwest::Client::new()
+            .post("https://photos.example.net/initiate")
+            .build()
+            .unwrap();
+        oauth1.sign_with(&mut request, "wIjqoS", 137131200)?;
+        assert_eq!(
+            request.headers()["authorization"],
+            r#"OAuth realm="Photos", oauth_consumer_key="dpf43f3p2l4k3l03", oauth_signature_method="PLAINTEXT", oauth_timestamp="137131200", oauth_nonce="wIjqoS", oauth_callback="http%3A%2F%2Fprinter.example.com%2Fready", oauth_signature="kd94hf93k423kf44%26""#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rsa_sha1_signs_with_private_key() -> Result<()> {
+        let oauth1 = OAuth1::rsa_sha1("dpf43f3p2l4k3l03", RSA_KEY)?;
+        let mut request = photos_request();
+        oauth1.sign_with(&mut request, "chapoH", 137131202)?;
+        let header = request.headers()["authorization"].to_str().unwrap();
+        assert!(header.contains(r#"oauth_signature_method="RSA-SHA1""#));
+        assert!(header.ends_with(r#"oauth_signature="kgwnAELbF7%2BfxB2KH6Ute9e7Pt18gLx2EZbPEOZ0IuNa27rH3uoKwyOOfiagab%2FuWMgcMtoxI7mLxzkCekMLoyqiP9qQUyL2WVmh3oAHoLkpHTlF%2F5P6dSlbUAVPfiAtSy3RzbVDybQGw7dsZW6NFDfvwPLHmFwVly89eUQJ1znrUdvaLViZVUPibh0L5CrpW98ipL5gGoqHTE4OSZUTvyucPtFVPy%2FopO7bA%2FH2Zp4eTrJQzjmq2Pl9We8BazjzM84LS5E6GJcB317eSFP99MWAt4eMt3ehaHf5TIwD5SLlAAbmRjt57a0XeUEoSFDy1KT9ZpR7iejF0N74vRj1Ng%3D%3D""#));
+
+        let mut pkcs1_request = photos_request();
+        OAuth1::rsa_sha1("dpf43f3p2l4k3l03", RSA_PKCS1_KEY)?.sign_with(
+            &mut pkcs1_request,
+            "chapoH",
+            137131202,
+        )?;
+        assert_eq!(
+            pkcs1_request.headers()["authorization"],
+            request.headers()["authorization"]
+        );
+
+        assert!(matches!(
+            OAuth1::rsa_sha1("dpf43f3p2l4k3l03", "not a key"),
+            Err(ApiError::RequestSigning(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn every_page_is_signed_with_a_new_nonce() -> Result<()> {
+        let server = MockServer::start();
+        let nonces = Arc::new(Mutex::new(HashSet::new()));
+        let seen = nonces.clone();
+        let pages = server.mock(|when, then| {
+            when.method("GET")
+                .path("/listings")
+                .query_param_exists("page[number]")
+                .is_true(move |request| {
+                    let headers = request.headers();
+                    let Some(header) = headers
+                        .get("authorization")
+                        .and_then(|value| value.to_str().ok())
+                    else {
+                        return false;
+                    };
+                    let nonce = header
+                        .split(", ")
+                        .find_map(|param| param.strip_prefix("oauth_nonce="));
+                    header.starts_with(r#"OAuth oauth_consumer_key="key", oauth_token="token""#)
+                        && header.contains("oauth_signature=")
+                        && nonce.is_some_and(|nonce| seen.lock().unwrap().insert(nonce.to_string()))
+                });
+            then.status(200)
+                .header("X-Total", "200")
+                .header("X-Per-Page", "100")
+                .json_body(json!([{"id": 1}]));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .oauth1(OAuth1::new("key", "secret").token("token", "token_secret"))
+            .build()
+            .pagination(PaginationRule::Fixed(2));
+
+        let listings: Vec<Value> = api.get("/listings")?.await?;
+        assert_eq!(listings.len(), 2);
+        pages.assert_calls(2);
+        assert_eq!(nonces.lock().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn oauth1_cannot_be_combined_with_a_signer() {
+        let api: Api = ApiBuilder::new("https://api.example.com")
+            .oauth1(OAuth1::new("key", "secret"))
+            .signer(HmacSigner::new("secret"))
+            .build();
+        assert!(matches!(
+            api.get::<Value>("/listings"),
+            Err(ApiError::RequestSigning(_))
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use httpmock::MockServer;
+use std::{sync::Arc, time::Duration};
+
+#[cfg(test)]
+mod oauth2_tests {
+    use reqt::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+
+    fn client(server: &MockServer) -> OAuth2Client {
+        OAuth2Client::new(server.url("/token"), "client_id").client_secret("client_secret")
+    }
+
+    #[tokio::test]
+    async fn token_is_refreshed_before_expiry() -> Result<()> {
+        let server = MockServer::start();
+        let first = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "client_credentials")
+                .form_urlencoded_tuple("client_secret", "client_secret");
+            then.status(200).json_body(json!({
+                "access_token": "first",
+                "expires_in": 1,
+                "refresh_token": "refresh"
+            }));
+        });
+        let refresh = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "refresh_token")
+                .form_urlencoded_tuple("refresh_token", "refresh");
+            then.status(200)
+                .json_body(json!({"access_token": "second", "expires_in": 3600}));
+        });
+        let source = TokenSource::new(client(&server), Grant::ClientCredentials);
+
+        assert_eq!(source.token().await?, "first");
+        assert_eq!(source.token().await?, "first");
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(source.token().await?, "second");
+        // The refresh token is kept when the server does not rotate it
+        assert_eq!(source.refresh_token().await.as_deref(), Some("refresh"));
+        first.assert_calls(1);
+        refresh.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_falls_back_to_grant() -> Result<()> {
+        let server = MockServer::start();
+        let grant = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "client_credentials");
+            then.status(200).json_body(json!({
+                "access_token": "token",
+                "expires_in": 1,
+                "refresh_token": "revoked"
+            }));
+        });
+        let refresh = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "refresh_token");
+            then.status(400)
+                .json_body(json!({"error": "invalid_grant"}));
+        });
+        let source = TokenSource::new(client(&server), Grant::ClientCredentials);
+
+        source.token().await?;
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        source.token().await?;
+        grant.assert_calls(2);
+        refresh.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_endpoint_error_is_decoded() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(401).json_body(json!({
+                "error": "invalid_client",
+                "error_description": "Unknown client"
+            }));
+        });
+        let source = TokenSource::new(client(&server), Grant::ClientCredentials);
+
+        let error = source.token().await.unwrap_err();
+        let body = error.api_body::<OAuth2Error>().unwrap();
+        assert_eq!(body.error, "invalid_client");
+        assert_eq!(body.error_description.as_deref(), Some("Unknown client"));
+    }
+
+    #[tokio::test]
+    async fn unauthorized_request_is_retried_once_with_new_token() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "client_credentials");
+            then.status(200).json_body(json!({
+                "access_token": "revoked",
+                "expires_in": 3600,
+                "refresh_token": "refresh"
+            }));
+        });
+        let refresh = server.mock(|when, then| {
+            when.method("POST")
+                .path("/token")
+                .form_urlencoded_tuple("grant_type", "refresh_token");
+            then.status(200)
+                .json_body(json!({"access_token": "renewed", "expires_in": 3600}));
+        });
+        let rejected = server.mock(|when, then| {
+            when.method("GET")
+                .path("/users")
+                .header("authorization", "Bearer revoked");
+            then.status(401);
+        });
+        let accepted = server.mock(|when, then| {
+            when.method("GET")
+                .path("/users")
+                .header("authorization", "Bearer renewed");
+            then.status(200).json_body(json!({"id": 1}));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .token_source(TokenSource::new(client(&server), Grant::ClientCredentials))
+            .build();
+
+        let user = api.get::<serde_json::Value>("/users")?.await?;
+        assert_eq!(user, json!({"id": 1}));
+        rejected.assert_calls(1);
+        accepted.assert_calls(1);
+        refresh.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unauthorized_request_is_not_retried_twice() -> Result<()> {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(200).json_body(json!({"access_token": "token"}));
+        });
+        let rejected = server.mock(|when, then| {
+            when.method("GET").path("/users");
+            then.status(401);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .token_source(TokenSource::new(client(&server), Grant::ClientCredentials))
+            .build();
+
+        let error = api.get::<()>("/users")?.await.unwrap_err();
+        assert_eq!(error.status(), Some(reqwest::StatusCode::UNAUTHORIZED));
+        rejected.assert_calls(2);
+        token.assert_calls(2);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_refreshes_share_one_request() -> Result<()> {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST").path("/token");
+            then.status(200)
+                .delay(Duration::from_millis(100))
+                .json_body(json!({"access_token": "token", "expires_in": 3600}));
+        });
+        let source = Arc::new(TokenSource::new(client(&server), Grant::ClientCredentials));
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let source = source.clone();
+                tokio::spawn(async move { source.token().await })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap()?, "token");
+        }
+        token.assert_calls(1);
+
+        // Requests rejected with the same token trigger a single renewal
+        for _ in 0..3 {
+            source.invalidate("token").await;
+        }
+        source.invalidate("unknown").await;
+        source.token().await?;
+        token.assert_calls(2);
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use httpmock::MockServer;
+
+#[cfg(test)]
+mod oidc_discovery_tests {
+    use base64::{Engine, engine::general_purpose};
+    use reqt::{Keycloak, OIDC, prelude::*};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    fn discovery<'a>(
+        server: &'a MockServer,
+        issuer_path: &str,
+        metadata: serde_json::Value,
+    ) -> httpmock::Mock<'a> {
+        let issuer = server.url(issuer_path);
+        let mut document = json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/auth"),
+            "token_endpoint": format!("{issuer}/token"),
+            "device_authorization_endpoint": format!("{issuer}/device"),
+        });
+        if let (Some(document), Some(metadata)) = (document.as_object_mut(), metadata.as_object()) {
+            document.extend(metadata.clone());
+        }
+        server.mock(|when, then| {
+            when.method("GET")
+                .path(format!("{issuer_path}/.well-known/openid-configuration"));
+            then.status(200).json_body(document);
+        })
+    }
+
+    #[tokio::test]
+    async fn discovery_is_cached() -> Result<()> {
+        let server = MockServer::start();
+        let document = discovery(
+            &server,
+            "/realms/cached",
+            json!({"grant_types_supported": ["authorization_code"]}),
+        );
+        let issuer = server.url("/realms/cached");
+
+        let client = reqwest::Client::new();
+        let provider = ProviderMetadata::discover(&client, &issuer).await?;
+        let again = ProviderMetadata::discover(&client, &format!("{issuer}/")).await?;
+        assert_eq!(provider, again);
+        assert_eq!(provider.token_endpoint, Some(format!("{issuer}/token")));
+        assert!(provider.supports_grant("authorization_code"));
+        assert!(!provider.supports_grant("client_credentials"));
+        // Without a list, only client_secret_basic is supported
+        assert!(provider.supports_auth_method("client_secret_basic"));
+        assert!(!provider.supports_auth_method("client_secret_post"));
+        assert!(provider.device_code_flow(provider.client("cli")?).is_err());
+        document.assert_calls(1);
+
+        ProviderMetadata::invalidate(&format!("{issuer}/"));
+        ProviderMetadata::discover(&client, &issuer).await?;
+        document.assert_calls(2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn issuer_mismatch_is_rejected() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/.well-known/openid-configuration");
+            then.status(200)
+                .json_body(json!({"issuer": "https://evil.example.com"}));
+        });
+
+        let result = ProviderMetadata::discover(&reqwest::Client::new(), &server.base_url()).await;
+        assert!(matches!(result, Err(ApiError::OAuth2Flow(_))));
+    }
+
+    #[tokio::test]
+    async fn malformed_document_is_a_discovery_error() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/.well-known/openid-configuration");
+            then.status(200).body("<html>maintenance</html>");
+        });
+
+        let result = ProviderMetadata::fetch(&reqwest::Client::new(), &server.base_url()).await;
+        assert!(matches!(result, Err(ApiError::DiscoveryParse(_))));
+    }
+
+    #[derive(Debug, Clone, Deserialize, OIDC)]
+    struct TestOidcConnector {
+        client_id: String,
+        client_secret: String,
+        issuer: String,
+        scopes: Vec<String>,
+    }
+
+    #[tokio::test]
+    async fn oidc_derive_uses_discovered_endpoint() -> Result<()> {
+        let server = MockServer::start();
+        discovery(
+            &server,
+            "/oidc",
+            json!({
+                "grant_types_supported": ["client_credentials"],
+                "token_endpoint_auth_methods_supported": ["client_secret_post", "client_secret_basic"]
+            }),
+        );
+        let basic = general_purpose::STANDARD.encode("cli:s%C3%A9cret");
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/oidc/token")
+                .header("authorization", format!("Basic {basic}"))
+                .form_urlencoded_tuple("grant_type", "client_credentials")
+                .form_urlencoded_tuple_missing("client_secret");
+            then.status(200)
+                .json_body(json!({"access_token": "oidc-token", "expires_in": 3600}));
+        });
+        let connector = TestOidcConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("sécret"),
+            issuer: server.url("/oidc"),
+            scopes: vec![String::from("openid")],
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer oidc-token");
+        assert_eq!(api.token_source().unwrap().token().await?, "oidc-token");
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn oidc_derive_rejects_unsupported_grant() {
+        let server = MockServer::start();
+        discovery(
+            &server,
+            "/unsupported",
+            json!({"grant_types_supported": ["authorization_code"]}),
+        );
+        let connector = TestOidcConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            issuer: server.url("/unsupported"),
+            scopes: Vec::new(),
+        };
+
+        let result = connector.connect(&server.base_url()).await;
+        assert!(matches!(result, Err(ApiError::OAuth2Flow(_))));
+    }
+
+    #[derive(Debug, Clone, Deserialize, Keycloak)]
+    #[auth_type(Bearer)]
+    struct TestKeycloakConnector {
+        client_id: String,
+        client_secret: String,
+        auth_endpoint: String,
+        realm: String,
+        user_login: String,
+        user_pass: String,
+    }
+
+    #[tokio::test]
+    async fn keycloak_derive_uses_discovered_endpoint() -> Result<()> {
+        let server = MockServer::start();
+        discovery(&server, "/realms/main", json!({}));
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/main/token")
+                .form_urlencoded_tuple("grant_type", "password")
+                .form_urlencoded_tuple("username", "user");
+            then.status(200)
+                .json_body(json!({"access_token": "keycloak-token"}));
+        });
+        let connector = TestKeycloakConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("main"),
+            user_login: String::from("user"),
+            user_pass: String::from("pass"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer keycloak-token");
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keycloak_derive_keeps_client_secret_basic() -> Result<()> {
+        let server = MockServer::start();
+        discovery(
+            &server,
+            "/realms/post",
+            json!({"token_endpoint_auth_methods_supported": ["client_secret_post"]}),
+        );
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/post/token")
+                .header(
+                    "authorization",
+                    format!("Basic {}", general_purpose::STANDARD.encode("cli:secret")),
+                )
+                .form_urlencoded_tuple_missing("client_secret");
+            then.status(200)
+                .json_body(json!({"access_token": "basic-token"}));
+        });
+        let connector = TestKeycloakConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("post"),
+            user_login: String::from("user"),
+            user_pass: String::from("pass"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer basic-token");
+        token.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keycloak_derive_falls_back_without_discovery() -> Result<()> {
+        let server = MockServer::start();
+        let token = server.mock(|when, then| {
+            when.method("POST")
+                .path("/realms/legacy/protocol/openid-connect/token")
+                .form_urlencoded_tuple("grant_type", "password")
+                .header(
+                    "authorization",
+                    format!("Basic {}", general_purpose::STANDARD.encode("cli:secret")),
+                )
+                .form_urlencoded_tuple_missing("client_secret");
+            then.status(200)
+                .json_body(json!({"access_token": "legacy-token"}));
+        });
+        let connector = TestKeycloakConnector {
+            client_id: String::from("cli"),
+            client_secret: String::from("secret"),
+            auth_endpoint: server.url("/"),
+            realm: String::from("legacy"),
+            user_login: String::from("user"),
+            user_pass: String::from("pass"),
+        };
+
+        let api = connector.connect(&server.base_url()).await?;
+        assert_eq!(api.token(), "Bearer legacy-token");
+        token.assert_calls(1);
+        Ok(())
+    }
+}
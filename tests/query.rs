@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+#[cfg(test)]
+mod query_tests {
+    use reqt::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn query_from_str() -> Result<()> {
+        let query = Query::from_str("?page=1&&sort=name&flag")?;
+        assert_eq!(query.to_string(), "?page=1&sort=name&flag");
+        assert_eq!(Query::from_str("")?.to_string(), "");
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_query_is_rejected() {
+        for malformed in ["=value", "page=1&=2", "name=jean michel", "page=1#top"] {
+            assert!(
+                matches!(malformed.try_into_query(), Err(ApiError::InvalidQuery(_))),
+                "{malformed} should be rejected"
+            );
+            assert!(Query::from_str(malformed).is_ok());
+        }
+    }
+
+    #[test]
+    fn query_try_into() -> Result<()> {
+        let query = "a=1"
+            .try_into_query()?
+            .join(String::from("b=2").try_into_query()?);
+        assert_eq!(query.to_string(), "?a=1&b=2");
+
+        let params = ["a=1", "b=2"];
+        assert_eq!(params[..].try_into_query()?.to_string(), "?a=1&b=2");
+        let params = [String::from("a=1"), String::from("=2")];
+        assert!(matches!(
+            params[..].try_into_query(),
+            Err(ApiError::InvalidQuery(param)) if param == "=2"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn query_from_keeps_every_param() {
+        let query: Query = "a=1&b=jean michel&c=3".into();
+        assert_eq!(query.to_string(), "?a=1&b=jean michel&c=3");
+
+        let params = ["a=1", "b 2"];
+        let query: Query = params[..].into();
+        assert_eq!(query.to_string(), "?a=1&b 2");
+    }
+
+    #[tokio::test]
+    async fn query_values_are_encoded() -> Result<()> {
+        let server = httpmock::MockServer::start();
+        let users = server.mock(|when, then| {
+            when.method("GET")
+                .path("/users")
+                .query_param("name", "jean michel");
+            then.status(200).body("[]");
+        });
+        let api: Api = ApiBuilder::new(server.base_url()).limit(100).build();
+
+        api.get::<Vec<serde_json::Value>>("/users")?
+            .query("name=jean michel")
+            .await?;
+        users.assert_calls(1);
+        Ok(())
+    }
+
+    #[test]
+    fn request_try_query() -> Result<()> {
+        let request = Request::<()>::new(
+            reqwest::Method::GET,
+            RequestUrl::new("http://localhost"),
+            None,
+            None,
+        );
+        assert!(request.clone().try_query("page=2").is_ok());
+        assert!(request.clone().try_query(("page", 2)).is_ok());
+        assert!(matches!(
+            request.try_query("page 2"),
+            Err(ApiError::InvalidQuery(_))
+        ));
+        Ok(())
+    }
+}
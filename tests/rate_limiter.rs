@@ -0,0 +1,431 @@
+#[cfg(test)]
+mod rate_limiter_tests {
+    use std::{sync::Arc, time::Duration};
+
+    use reqt::{
+        rate_limit_bucket::{RateLimitBucket, RateLimitBuckets},
+        rate_limit_strategy::{FixedWindow, Gcra, RateLimitStrategy, SlidingLog, TokenBucket},
+        rate_limiter::{RateLimiter, TimePeriod},
+    };
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use tokio::{sync::Mutex, time::Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_until_window_reset() {
+        let mut limiter = RateLimiter::new(2, TimePeriod::Minute);
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+        assert_eq!(limiter.remaining(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_does_not_block_the_runtime() {
+        let limiter = Arc::new(Mutex::new(RateLimiter::new(1, TimePeriod::Hour)));
+        limiter.lock().await.acquire().await;
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.lock().await.acquire().await })
+        };
+        let start = Instant::now();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(!waiter.is_finished());
+
+        waiter.await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(3600));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiters_are_served_in_order() {
+        let limiter = Arc::new(Mutex::new(RateLimiter::new(1, TimePeriod::Second)));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for i in 0..4 {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            handles.push(tokio::spawn(async move {
+                RateLimiter::acquire_shared(&limiter).await;
+                order.lock().await.push(i);
+            }));
+            tokio::task::yield_now().await;
+        }
+        let start = Instant::now();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(*order.lock().await, vec![0, 1, 2, 3]);
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_wait_releases_the_lock() {
+        let limiter = Arc::new(Mutex::new(RateLimiter::new(1, TimePeriod::Hour)));
+        RateLimiter::acquire_shared(&limiter).await;
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { RateLimiter::acquire_shared(&limiter).await })
+        };
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let mut locked = limiter
+            .try_lock()
+            .expect("the waiter should not hold the lock");
+        assert!(locked.is_asleep);
+        assert!(!waiter.is_finished());
+        // Raising the limit applies once the waiter checks the limit again
+        locked.limit = 2;
+        drop(locked);
+
+        let start = Instant::now();
+        waiter.await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(3599));
+        assert!(!limiter.lock().await.is_asleep);
+    }
+
+    const PERIOD: Duration = Duration::from_secs(10);
+
+    /// Send `count` requests at `at` and return how many were allowed
+    fn burst(strategy: &mut dyn RateLimitStrategy, at: Instant, count: u32) -> u32 {
+        (0..count)
+            .filter(|_| strategy.try_acquire(5, PERIOD, at).is_none())
+            .count() as u32
+    }
+
+    #[test]
+    fn fixed_window_allows_bursts_at_window_edges() {
+        let start = Instant::now();
+        let mut strategy = FixedWindow::default();
+        assert_eq!(burst(&mut strategy, start + Duration::from_secs(9), 5), 5);
+        assert_eq!(burst(&mut strategy, start + Duration::from_secs(19), 5), 5);
+        assert_eq!(
+            strategy.try_acquire(5, PERIOD, start + Duration::from_secs(20)),
+            Some(Duration::from_secs(9))
+        );
+    }
+
+    #[test]
+    fn sliding_log_limits_any_period() {
+        let start = Instant::now();
+        let mut strategy = SlidingLog::default();
+        assert_eq!(burst(&mut strategy, start, 3), 3);
+        assert_eq!(burst(&mut strategy, start + Duration::from_secs(9), 5), 2);
+        assert_eq!(
+            strategy.try_acquire(5, PERIOD, start + Duration::from_secs(9)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            strategy.remaining(5, PERIOD, start + Duration::from_secs(10)),
+            3
+        );
+        assert_eq!(burst(&mut strategy, start + Duration::from_secs(10), 5), 3);
+    }
+
+    #[test]
+    fn token_bucket_refills_continuously() {
+        let start = Instant::now();
+        let mut strategy = TokenBucket::default();
+        assert_eq!(burst(&mut strategy, start, 6), 5);
+        assert_eq!(
+            strategy.try_acquire(5, PERIOD, start),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(burst(&mut strategy, start + Duration::from_secs(4), 5), 2);
+        assert_eq!(
+            strategy.remaining(5, PERIOD, start + Duration::from_secs(60)),
+            5
+        );
+    }
+
+    #[test]
+    fn gcra_spaces_requests_after_burst() {
+        let start = Instant::now();
+        let mut strategy = Gcra::default();
+        assert_eq!(burst(&mut strategy, start, 6), 5);
+        assert_eq!(
+            strategy.try_acquire(5, PERIOD, start),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(burst(&mut strategy, start + Duration::from_secs(2), 2), 1);
+        assert_eq!(
+            strategy.remaining(5, PERIOD, start + Duration::from_secs(12)),
+            5
+        );
+    }
+
+    #[test]
+    fn zero_limit_is_handled_as_one() {
+        let start = Instant::now();
+        let strategies: [Box<dyn RateLimitStrategy>; 3] = [
+            Box::new(SlidingLog::default()),
+            Box::new(TokenBucket::default()),
+            Box::new(Gcra::default()),
+        ];
+        for mut strategy in strategies {
+            assert_eq!(strategy.try_acquire(0, PERIOD, start), None);
+            assert_eq!(strategy.try_acquire(0, PERIOD, start), Some(PERIOD));
+            assert_eq!(strategy.remaining(0, PERIOD, start), 0);
+            strategy.sync(0, PERIOD, 0, PERIOD, start);
+            assert!(strategy.wait_time(0, PERIOD, start).is_some());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limiter_uses_strategy() {
+        let mut limiter = RateLimiter::new(2, TimePeriod::Second).strategy(Gcra::default());
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_from_ietf_headers() {
+        let mut limiter = RateLimiter::new(100, TimePeriod::Second);
+        limiter.update(&headers(&[
+            ("RateLimit-Policy", "10;w=60".to_string()),
+            ("RateLimit-Limit", "10".to_string()),
+            ("RateLimit-Remaining", "1".to_string()),
+            ("RateLimit-Reset", "30".to_string()),
+        ]));
+        assert_eq!(limiter.limit, 100);
+        assert_eq!(limiter.tiers.len(), 1);
+        assert_eq!(limiter.tiers[0].limit, 10);
+        assert_eq!(limiter.tiers[0].period, TimePeriod::Minute);
+        assert_eq!(limiter.remaining(), 1);
+
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(30));
+        assert_eq!(limiter.remaining(), 9);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_from_named_ietf_policy() {
+        let mut limiter = RateLimiter::new(100, TimePeriod::Second);
+        limiter.update(&headers(&[(
+            "RateLimit-Policy",
+            "\"burst\";q=20;w=1, \"daily\";q=1000;w=86400".to_string(),
+        )]));
+        assert_eq!(limiter.limit, 20);
+        assert_eq!(limiter.period, TimePeriod::Second);
+        assert_eq!(limiter.tiers.len(), 1);
+        assert_eq!(limiter.tiers[0].limit, 1000);
+        assert_eq!(limiter.tiers[0].period, TimePeriod::Day);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reported_limit_without_period_is_not_applied() {
+        let mut limiter = RateLimiter::new(100, TimePeriod::Minute).tier(1000, TimePeriod::Day);
+        limiter.update(&headers(&[
+            ("X-RateLimit-Limit", "50".to_string()),
+            ("X-RateLimit-Remaining", "40".to_string()),
+        ]));
+        assert_eq!(limiter.limit, 100);
+        assert_eq!(limiter.tiers[0].limit, 1000);
+
+        limiter.update(&headers(&[
+            ("RateLimit-Policy", "50;w=86400".to_string()),
+            ("RateLimit-Limit", "50".to_string()),
+        ]));
+        assert_eq!(limiter.limit, 100);
+        assert_eq!(limiter.tiers[0].limit, 50);
+        assert_eq!(limiter.tiers[0].period, TimePeriod::Day);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mismatched_reported_quota_keeps_the_local_limit() {
+        let mut limiter = RateLimiter::new(1, TimePeriod::Second);
+        let github = headers(&[
+            ("X-RateLimit-Limit", "5000".to_string()),
+            ("X-RateLimit-Remaining", "4999".to_string()),
+            ("X-RateLimit-Reset", "3600".to_string()),
+        ]);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+            limiter.update(&github);
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(limiter.limit, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_from_x_ratelimit_epoch_reset() {
+        let mut limiter = RateLimiter::new(5000, TimePeriod::Hour);
+        let reset = chrono::Utc::now().timestamp() + 20;
+        limiter.update(&headers(&[
+            ("X-RateLimit-Limit", "5000".to_string()),
+            ("X-RateLimit-Remaining", "0".to_string()),
+            ("X-RateLimit-Reset", reset.to_string()),
+        ]));
+        assert_eq!(limiter.remaining(), 0);
+
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert!(start.elapsed() > Duration::from_secs(18));
+        assert!(start.elapsed() <= Duration::from_secs(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_from_x_ratelimit_delta_reset() {
+        let mut limiter =
+            RateLimiter::new(100, TimePeriod::Minute).strategy(TokenBucket::default());
+        limiter.update(&headers(&[
+            ("X-RateLimit-Limit", "100".to_string()),
+            ("X-RateLimit-Remaining", "0".to_string()),
+            ("X-RateLimit-Reset", "12".to_string()),
+        ]));
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(12));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_from_retry_after() {
+        let mut limiter = RateLimiter::new(10, TimePeriod::Second).strategy(SlidingLog::default());
+        limiter.update(&headers(&[("Retry-After", "5".to_string())]));
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+
+        let date = (chrono::Utc::now() + chrono::TimeDelta::seconds(90)).to_rfc2822();
+        let mut limiter = RateLimiter::new(10, TimePeriod::Second).strategy(Gcra::default());
+        limiter.update(&headers(&[("Retry-After", date.replace("+0000", "GMT"))]));
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert!(start.elapsed() > Duration::from_secs(88));
+        assert!(start.elapsed() <= Duration::from_secs(90));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_ignored_when_not_adaptive() {
+        let mut limiter = RateLimiter::new(10, TimePeriod::Second).is_adaptive(false);
+        limiter.update(&headers(&[
+            ("RateLimit-Limit", "1".to_string()),
+            ("Retry-After", "5".to_string()),
+        ]));
+        assert_eq!(limiter.limit, 10);
+        assert_eq!(limiter.remaining(), 10);
+    }
+
+    #[test]
+    fn bucket_matches_route_prefix_and_pattern() {
+        let search = RateLimitBucket::new("search", "/search", RateLimiter::default());
+        assert!(search.matches("/search"));
+        assert!(search.matches("search/issues?q=reqt"));
+        assert!(!search.matches("/searching"));
+        assert!(!search.matches("/users"));
+
+        let issues = RateLimitBucket::new("issues", "/repos/*/issues", RateLimiter::default());
+        assert!(issues.matches("/repos/reqt/issues/42"));
+        assert!(!issues.matches("/repos/reqt/pulls"));
+        assert!(!issues.matches("/repos/reqt"));
+    }
+
+    #[tokio::test]
+    async fn buckets_select_first_match_or_fallback() {
+        let fallback = Arc::new(Mutex::new(RateLimiter::new(5000, TimePeriod::Hour)));
+        let buckets = RateLimitBuckets::new(
+            vec![
+                RateLimitBucket::new(
+                    "search",
+                    "/search",
+                    RateLimiter::new(30, TimePeriod::Minute),
+                ),
+                RateLimitBucket::new("all", "/", RateLimiter::new(1, TimePeriod::Second)),
+            ],
+            fallback.clone(),
+        );
+        assert_eq!(buckets.limiter_for("/search/code").lock().await.limit, 30);
+        assert_eq!(buckets.limiter_for("/users").lock().await.limit, 1);
+        assert!(buckets.named("SEARCH").is_some());
+        assert!(buckets.named("core").is_none());
+        assert_eq!(
+            RateLimitBuckets::new(Vec::new(), fallback)
+                .limiter_for("/users")
+                .lock()
+                .await
+                .limit,
+            5000
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tiers_wait_for_most_restrictive() {
+        let mut limiter = RateLimiter::new(2, TimePeriod::Second)
+            .tier(3, TimePeriod::Custom(Duration::from_secs(900)));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(limiter.remaining(), 0);
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(900));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_fills_matching_tiers() {
+        let mut limiter = RateLimiter::new(100, TimePeriod::Second).tier(50_000, TimePeriod::Day);
+        limiter.update(&headers(&[
+            ("x-secondly-ratelimit-limit", "10".to_string()),
+            ("x-dayly-ratelimit-limit", "10000".to_string()),
+            ("x-hourly-ratelimit-limit", "1000".to_string()),
+            ("RateLimit-Policy", "450;w=900".to_string()),
+        ]));
+        assert_eq!(limiter.limit, 10);
+        let tiers: Vec<(u32, TimePeriod)> = limiter
+            .tiers
+            .iter()
+            .map(|tier| (tier.limit, tier.period.clone()))
+            .collect();
+        assert_eq!(
+            tiers,
+            vec![
+                (10_000, TimePeriod::Day),
+                (1000, TimePeriod::Hour),
+                (450, TimePeriod::Custom(Duration::from_secs(900))),
+            ]
+        );
+    }
+
+    #[test]
+    fn time_period_from_duration() {
+        assert_eq!(
+            TimePeriod::from(Duration::from_secs(60)),
+            TimePeriod::Minute
+        );
+        assert_eq!(
+            TimePeriod::from(Duration::from_secs(900)),
+            TimePeriod::Custom(Duration::from_secs(900))
+        );
+    }
+
+    #[test]
+    fn invalid_period_limit_header_is_ignored() {
+        let mut limiter = RateLimiter::new(5, TimePeriod::Second);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-secondly-ratelimit-limit",
+            HeaderValue::from_bytes(b"\xff10").unwrap(),
+        );
+        limiter.update(&headers);
+        assert_eq!(limiter.limit, 5);
+    }
+}
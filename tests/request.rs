@@ -1,10 +1,14 @@
 use fake::{Dummy, Fake, Faker};
 use httpmock::MockServer;
 use serde::{Deserialize, Serialize};
+use std::future::IntoFuture;
 
 #[cfg(test)]
 mod request_tests {
-    use reqt::prelude::*;
+    use reqt::{
+        prelude::*,
+        rate_limiter::{RateLimiter, TimePeriod},
+    };
     use serial_test::serial;
 
     use super::*;
@@ -814,4 +818,643 @@ mod request_tests {
         assert_eq!(response.len(), PAGINATION_SIZE);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn request_with_shared_client() -> Result<()> {
+        let server = mock_server();
+        let user_agent_mock = server.mock(|when, then| {
+            when.method("GET")
+                .path("/users/agent")
+                .header("user-agent", "reqt-test");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body_obj(&Vec::<User>::new());
+        });
+        let client = reqwest::Client::builder()
+            .user_agent("reqt-test")
+            .build()
+            .map_err(ApiError::ReqwestBuilder)?;
+        let api: Api = ApiBuilder::new(server.base_url()).client(client).build();
+        let users: Vec<User> = api.get("/users/agent")?.await?;
+        assert!(users.is_empty());
+        user_agent_mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_pages_with_pool_settings() -> Result<()> {
+        let server = mock_server();
+        let api: Api = ApiBuilder::new(server.base_url())
+            .pool_idle_timeout(std::time::Duration::from_secs(30))
+            .pool_max_idle_per_host(4)
+            .tcp_keepalive(std::time::Duration::from_secs(60))
+            .try_build()?;
+        let users: Vec<User> = api
+            .get("/users")?
+            .pagination(PaginationRule::OneShot)
+            .await?;
+        assert_eq!(users.len(), 1000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_updates_bucket_named_by_server() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/users");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .header("X-RateLimit-Resource", "search")
+                .header("X-RateLimit-Limit", "30")
+                .header("X-RateLimit-Remaining", "0")
+                .header("X-RateLimit-Reset", "600")
+                .json_body_obj(&Vec::<User>::new());
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .limit_bucket(
+                "search",
+                "/search",
+                RateLimiter::new(30, TimePeriod::Minute),
+            )
+            .build();
+        let _: Vec<User> = api.get("/users")?.await?;
+
+        let buckets = api.rate_limit_buckets();
+        let search = buckets.named("search").expect("search bucket");
+        assert_eq!(search.lock().await.remaining(), 0);
+        assert_eq!(buckets.limiter_for("/users").lock().await.remaining(), 99);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_respects_max_concurrency() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/slow");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .delay(std::time::Duration::from_millis(200))
+                .json_body_obj(&Vec::<User>::new());
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .max_concurrency(2)
+            .build();
+
+        let start = std::time::Instant::now();
+        let mut handles = Vec::new();
+        for _ in 0..6 {
+            let request = api.get::<Vec<User>>("/slow")?;
+            handles.push(tokio::spawn(request.into_future()));
+        }
+        for handle in handles {
+            let users = handle.await.expect("request task")?;
+            assert!(users.is_empty());
+        }
+        assert!(start.elapsed() >= std::time::Duration::from_millis(600));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rate_setters_apply_while_a_request_waits() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/users");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body_obj(&Vec::<User>::new());
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(1)
+            .limit_period(TimePeriod::Hour)
+            .build();
+        api.get::<Vec<User>>("/users")?.await?;
+        let waiting = tokio::spawn(api.get::<Vec<User>>("/users")?.into_future());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let api = api.rate_limit(10).rate_period(TimePeriod::Minute);
+        {
+            let limiter = api.rate_limit_buckets().limiter_for("/users");
+            let limiter = limiter.lock().await;
+            assert_eq!(limiter.limit, 10);
+            assert_eq!(limiter.period, TimePeriod::Minute);
+        }
+
+        tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            api.update_rate_limiter(|limiter| limiter.limit = 20),
+        )
+        .await
+        .expect("the update should not wait for the rate limit");
+        let limiter = api.rate_limit_buckets().limiter_for("/users");
+        assert_eq!(limiter.lock().await.limit, 20);
+        assert!(!waiting.is_finished());
+        waiting.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rate_setter_replaces_a_locked_limiter() {
+        let api: Api = ApiBuilder::new("http://localhost").limit(100).build();
+        let shared = api.rate_limit_buckets().limiter_for("/users");
+        let guard = shared.lock().await;
+
+        let api = api.rate_limit(5);
+        let limiter = api.rate_limit_buckets().limiter_for("/users");
+        assert_eq!(limiter.lock().await.limit, 5);
+        assert_eq!(guard.limit, 100);
+    }
+
+    #[tokio::test]
+    async fn zero_max_concurrency_does_not_block() -> Result<()> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/users");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body_obj(&Vec::<User>::new());
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .max_concurrency(0)
+            .build();
+
+        let request = api.get::<Vec<User>>("/users")?;
+        let users = tokio::time::timeout(std::time::Duration::from_secs(5), request)
+            .await
+            .expect("the request should not wait for a slot forever")?;
+        assert!(users.is_empty());
+        mock.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_retries_server_errors_with_backoff() -> Result<()> {
+        let server = MockServer::start();
+        let unavailable = server.mock(|when, then| {
+            when.method("GET").path("/unavailable");
+            then.status(503);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(
+                RetryPolicy::new(3)
+                    .base_backoff(std::time::Duration::from_millis(50))
+                    .jitter(false),
+            )
+            .build();
+
+        let start = std::time::Instant::now();
+        let result = api.get::<Vec<User>>("/unavailable")?.await;
+        assert!(result.is_err());
+        unavailable.assert_calls(3);
+        assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_succeeds_after_retry() -> Result<()> {
+        let server = MockServer::start();
+        let mut unavailable = server.mock(|when, then| {
+            when.method("GET").path("/flaky");
+            then.status(502);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(
+                RetryPolicy::new(2)
+                    .base_backoff(std::time::Duration::from_millis(300))
+                    .jitter(false),
+            )
+            .build();
+
+        let request = tokio::spawn(api.get::<Vec<User>>("/flaky")?.into_future());
+        while unavailable.calls() == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        unavailable.delete();
+        let available = server.mock(|when, then| {
+            when.method("GET").path("/flaky");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body_obj(&Vec::<User>::new());
+        });
+
+        let users = request.await.expect("request task")?;
+        assert!(users.is_empty());
+        available.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_honors_retry_after() -> Result<()> {
+        let server = MockServer::start();
+        let limited = server.mock(|when, then| {
+            when.method("GET").path("/limited");
+            then.status(429).header("Retry-After", "1");
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(RetryPolicy::new(2).base_backoff(std::time::Duration::from_millis(10)))
+            .build();
+
+        let start = std::time::Instant::now();
+        let result = api.get::<Vec<User>>("/limited")?.await;
+        assert_eq!(
+            result.err().and_then(|e| e.status()),
+            Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+        );
+        limited.assert_calls(2);
+        assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_does_not_retry_client_errors() -> Result<()> {
+        let server = MockServer::start();
+        let bad_request = server.mock(|when, then| {
+            when.method("GET").path("/invalid");
+            then.status(400);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(RetryPolicy::new(3))
+            .build();
+
+        let result = api
+            .get::<Vec<User>>("/invalid")?
+            .retry_policy(RetryPolicy::new(5))
+            .await;
+        assert_eq!(
+            result.err().and_then(|e| e.status()),
+            Some(reqwest::StatusCode::BAD_REQUEST)
+        );
+        bad_request.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn deprecated_force_limit_retries_too_many_requests() -> Result<()> {
+        let server = MockServer::start();
+        let limited = server.mock(|when, then| {
+            when.method("GET").path("/limited");
+            then.status(429).header("Retry-After", "0");
+        });
+        let unavailable = server.mock(|when, then| {
+            when.method("GET").path("/unavailable");
+            then.status(503);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .force_limit(2)
+            .build();
+
+        assert!(api.get::<Vec<User>>("/limited")?.await.is_err());
+        limited.assert_calls(3);
+        assert!(api.get::<Vec<User>>("/unavailable")?.await.is_err());
+        unavailable.assert_calls(1);
+
+        let api = api.force_limit(None);
+        assert!(api.get::<Vec<User>>("/limited")?.await.is_err());
+        assert!(
+            api.get::<Vec<User>>("/limited")?
+                .force_limite(Some(1))
+                .await
+                .is_err()
+        );
+        limited.assert_calls(6);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retries_and_pages_wait_for_the_rate_limit() -> Result<()> {
+        let server = MockServer::start();
+        let limited = server.mock(|when, then| {
+            when.method("GET").path("/limited");
+            then.status(429);
+        });
+        let pages = server.mock(|when, then| {
+            when.method("GET").path("/pages");
+            then.status(200)
+                .header("X-Total", "300")
+                .header("X-Per-Page", "100")
+                .json_body_obj(&vec![Faker.fake::<User>()]);
+        });
+        let retried: Api = ApiBuilder::new(server.base_url())
+            .limit(2)
+            .limit_period(TimePeriod::Minute)
+            .retry_policy(
+                RetryPolicy::new(5)
+                    .base_backoff(std::time::Duration::from_millis(1))
+                    .jitter(false),
+            )
+            .build();
+        let paginated: Api = ApiBuilder::new(server.base_url())
+            .limit(2)
+            .limit_period(TimePeriod::Minute)
+            .build()
+            .pagination(PaginationRule::Fixed(3));
+
+        let timeout = std::time::Duration::from_millis(500);
+        let result = tokio::time::timeout(timeout, retried.get::<Vec<User>>("/limited")?).await;
+        assert!(result.is_err(), "the third attempt should wait");
+        limited.assert_calls(2);
+        let result = tokio::time::timeout(timeout, paginated.get::<Vec<User>>("/pages")?).await;
+        assert!(result.is_err(), "the third page should wait");
+        pages.assert_calls(2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_does_not_retry_post_by_default() -> Result<()> {
+        let server = MockServer::start();
+        let orders = server.mock(|when, then| {
+            when.method("POST").path("/orders");
+            then.status(503);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(RetryPolicy::new(3).base_backoff(std::time::Duration::from_millis(10)))
+            .build();
+
+        let result = api.post::<Vec<User>, ()>("/orders")?.await;
+        assert!(result.is_err());
+        orders.assert_calls(1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_retries_post_with_stable_idempotency_key() -> Result<()> {
+        let server = MockServer::start();
+        let keys = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+        let recorded_keys = keys.clone();
+        let orders = server.mock(|when, then| {
+            when.method("POST")
+                .path("/orders")
+                .header_exists("X-Request-Key")
+                .is_true(move |request| {
+                    for (name, value) in request.headers_vec() {
+                        if name.eq_ignore_ascii_case("X-Request-Key") {
+                            recorded_keys.lock().unwrap().push(value.clone());
+                        }
+                    }
+                    true
+                });
+            then.status(503);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(
+                RetryPolicy::new(3)
+                    .base_backoff(std::time::Duration::from_millis(10))
+                    .retry_non_idempotent(true),
+            )
+            .idempotency_header(reqwest::header::HeaderName::from_static("x-request-key"))
+            .build();
+
+        let result = api.post::<Vec<User>, ()>("/orders")?.await;
+        assert!(result.is_err());
+        orders.assert_calls(3);
+        let keys = keys.lock().unwrap();
+        assert!(!keys.is_empty());
+        assert!(keys.iter().all(|key| key == &keys[0]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_error_keeps_the_response() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/forbidden");
+            then.status(403)
+                .header("X-Request-Id", "42")
+                .body("{\"message\":\"missing scope\"}");
+        });
+        let api: Api = ApiBuilder::new(server.base_url()).limit(100).build();
+
+        let result = api
+            .get::<Vec<User>>("/forbidden")?
+            .query(Query::new().add("access_token", "secret").add("id", 7))
+            .await;
+        let Err(ApiError::Http(error)) = result else {
+            panic!("expected an HTTP error, got {result:?}");
+        };
+        assert_eq!(error.status, reqwest::StatusCode::FORBIDDEN);
+        assert_eq!(error.method, reqwest::Method::GET);
+        assert_eq!(error.body, "{\"message\":\"missing scope\"}");
+        assert_eq!(error.headers["X-Request-Id"], "42");
+        assert!(error.url.contains("access_token=REDACTED"));
+        assert!(error.url.contains("id=7"));
+        assert!(!error.url.contains("secret"));
+
+        let error = ApiError::Http(error);
+        assert!(error.is_client_error());
+        assert!(!error.is_server_error());
+        assert!(!error.is_retryable());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_status_errors_keep_their_status() {
+        let error = ApiError::from(reqwest::StatusCode::TOO_MANY_REQUESTS);
+        assert!(matches!(error, ApiError::TooManyRequests));
+        assert_eq!(error.status(), Some(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn request_error_body_is_capped() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/unavailable");
+            then.status(503).body("x".repeat(HTTP_ERROR_BODY_LIMIT * 2));
+        });
+        let api: Api = ApiBuilder::new(server.base_url()).limit(100).build();
+
+        let error = api
+            .get::<Vec<User>>("/unavailable")?
+            .await
+            .expect_err("503 response");
+        assert!(error.is_server_error());
+        assert!(error.is_retryable());
+        let ApiError::Http(error) = error else {
+            panic!("expected an HTTP error");
+        };
+        assert_eq!(error.body.len(), HTTP_ERROR_BODY_LIMIT);
+        Ok(())
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct VendorError {
+        code: String,
+        message: String,
+    }
+
+    #[tokio::test]
+    async fn request_decodes_problem_details() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/accounts");
+            then.status(422)
+                .header("Content-Type", "application/problem+json")
+                .body(
+                    r#"{"type":"https://example.com/probs/out-of-credit","title":"Out of credit","status":422,"detail":"Balance is 30","balance":30}"#,
+                );
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .error_decoder(ProblemDetailsDecoder)
+            .build();
+
+        let error = api
+            .get::<Vec<User>>("/accounts")?
+            .await
+            .expect_err("422 response");
+        assert_eq!(
+            error.status(),
+            Some(reqwest::StatusCode::UNPROCESSABLE_ENTITY)
+        );
+        let problem = error.api_body::<ProblemDetails>().expect("problem details");
+        assert_eq!(problem.title.as_deref(), Some("Out of credit"));
+        assert_eq!(problem.status, Some(422));
+        assert_eq!(problem.extensions["balance"], 30);
+        assert_eq!(problem.to_string(), "Out of credit: Balance is 30");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_decodes_typed_error_body() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/orders");
+            then.status(409)
+                .header("Content-Type", "application/json")
+                .body(r#"{"code":"duplicate","message":"Order already exists"}"#);
+        });
+        server.mock(|when, then| {
+            when.method("GET").path("/broken");
+            then.status(409).body("upstream exploded");
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .error_body::<VendorError>()
+            .build();
+
+        let error = api
+            .get::<Vec<User>>("/orders")?
+            .await
+            .expect_err("409 response");
+        let ApiError::Api(response) = &error else {
+            panic!("expected a decoded error, got {error:?}");
+        };
+        let body = response.body::<VendorError>().expect("vendor error");
+        assert_eq!(body.code, "duplicate");
+        assert_eq!(body.message, "Order already exists");
+        assert!(response.body::<ProblemDetails>().is_none());
+
+        let error = api
+            .get::<Vec<User>>("/broken")?
+            .await
+            .expect_err("409 response");
+        let ApiError::Http(error) = error else {
+            panic!("expected the raw error, got {error:?}");
+        };
+        assert_eq!(error.body, "upstream exploded");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_overrides_error_decoder() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/orders");
+            then.status(400)
+                .body(r#"{"code":"invalid","message":"Missing id"}"#);
+        });
+        let api: Api = ApiBuilder::new(server.base_url()).limit(100).build();
+
+        let error = api
+            .get::<Vec<User>>("/orders")?
+            .error_body::<VendorError>()
+            .await
+            .expect_err("400 response");
+        assert_eq!(
+            error.api_body::<VendorError>().map(|e| e.code.as_str()),
+            Some("invalid")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn parse_error_gives_path_and_excerpt() -> Result<()> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/users/broken");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"[{"id":1,"name":"jean","primary_campus_id":31},{"id":2,"name":"michel","primary_campus_id":"paris"}]"#);
+        });
+        let api: Api = ApiBuilder::new(server.base_url()).limit(100).build();
+
+        let error = api
+            .get::<Vec<User>>("/users/broken")?
+            .query(("token", "secret"))
+            .await
+            .expect_err("invalid campus id");
+        let ApiError::ResponseParse(error) = error else {
+            panic!("expected a parse error, got {error:?}");
+        };
+        assert_eq!(error.path, "[1].primary_campus_id");
+        assert!(error.type_name.contains("User"));
+        assert!(error.url.contains("/users/broken"));
+        assert!(!error.url.contains("secret"));
+        assert!(error.excerpt.contains(r#""primary_campus_id":"paris""#));
+        assert_eq!(error.page, None);
+        assert_eq!(error.item, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn parse_error_gives_page_and_item() -> Result<()> {
+        let server = MockServer::start();
+        let page = |number: &'static str, body: &'static str| {
+            server.mock(move |when, then| {
+                when.method("GET")
+                    .path("/users/paged")
+                    .query_param("page[number]", number);
+                then.status(200)
+                    .header("Content-Type", "application/json")
+                    .header("X-Total", "4")
+                    .header("X-Per-Page", "2")
+                    .body(body);
+            });
+        };
+        page(
+            "1",
+            r#"[{"id":1,"name":"a","primary_campus_id":31},{"id":2,"name":"b","primary_campus_id":31}]"#,
+        );
+        page(
+            "2",
+            r#"[{"id":3,"name":"c","primary_campus_id":31},{"id":4,"name":null,"primary_campus_id":31}]"#,
+        );
+        let api: Api = ApiBuilder::new(server.base_url()).limit(100).build();
+
+        let error = api
+            .get::<Vec<User>>("/users/paged")?
+            .pagination(PaginationRule::OneShot)
+            .await
+            .expect_err("null name");
+        let ApiError::ResponseParse(error) = error else {
+            panic!("expected a parse error, got {error:?}");
+        };
+        assert_eq!(error.path, "[3].name");
+        assert_eq!(error.page, Some(2));
+        assert_eq!(error.item, Some(1));
+        assert!(error.excerpt.contains(r#""id":4"#));
+        assert!(error.to_string().contains("page 2, item 1"));
+        Ok(())
+    }
 }
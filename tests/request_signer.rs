@@ -0,0 +1,184 @@
+use httpmock::{HttpMockRequest, MockServer};
+
+#[cfg(test)]
+mod request_signer_tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use chrono::{TimeZone, Utc};
+    use hmac::{Hmac, Mac};
+    use reqt::prelude::*;
+    use serde_json::{Value, json};
+    use sha2::Sha256;
+
+    use super::*;
+
+    const SECRET: &str = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+
+    /// Check the signature of a request signed with the default parts
+    fn is_signed(request: &HttpMockRequest) -> bool {
+        let headers = request.headers();
+        let (Some(timestamp), Some(signature)) =
+            (headers.get("x-timestamp"), headers.get("x-signature"))
+        else {
+            return false;
+        };
+        let uri = request.uri();
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(request.method_str().as_bytes());
+        mac.update(uri.path().as_bytes());
+        mac.update(uri.query().unwrap_or_default().as_bytes());
+        mac.update(request.body_ref());
+        let expected: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        signature.as_bytes() == expected.as_bytes()
+    }
+
+    #[test]
+    fn signature_in_query() -> Result<()> {
+        // Example of the Binance API documentation
+        let signer = HmacSigner::new(SECRET)
+            .key(
+                Placement::Header(String::from("X-MBX-APIKEY")),
+                "vmPUZE6mv9SD5VNHk4HlWFsOr6aKE2zvsw0MuIgwCIPy6utIco14y7Ju91duEh8A",
+            )
+            .timestamp(
+                Placement::Query(String::from("timestamp")),
+                TimestampFormat::Milliseconds,
+            )
+            .signature(Placement::Query(String::from("signature")))
+            .parts(vec![SignedPart::Query, SignedPart::Body]);
+        let mut request = reqwest::Client::new()
+            .post("https://api.binance.com/api/v3/order?symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000")
+            .build()
+            .unwrap();
+        signer.sign_at(
+            &mut request,
+            Utc.timestamp_millis_opt(1499827319559).unwrap(),
+        )?;
+        assert_eq!(
+            request.url().query(),
+            Some(
+                "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559&signature=c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+            )
+        );
+        assert!(request.headers().contains_key("x-mbx-apikey"));
+        Ok(())
+    }
+
+    #[test]
+    fn base64_signature_in_headers() -> Result<()> {
+        let signer = HmacSigner::from_base64_secret("Y29pbmJhc2Utc2VjcmV0LWtleQ==")?
+            .key(Placement::Header(String::from("CB-ACCESS-KEY")), "key")
+            .timestamp(
+                Placement::Header(String::from("CB-ACCESS-TIMESTAMP")),
+                TimestampFormat::Seconds,
+            )
+            .signature(Placement::Header(String::from("CB-ACCESS-SIGN")))
+            .encoding(SignatureEncoding::Base64)
+            .parts(vec![
+                SignedPart::Timestamp,
+                SignedPart::Method,
+                SignedPart::PathAndQuery,
+                SignedPart::Body,
+            ]);
+        let mut request = reqwest::Client::new()
+            .post("https://api.example.com/orders?product=BTC-USD")
+            .body(r#"{"size":"0.01"}"#)
+            .build()
+            .unwrap();
+        signer.sign_at(&mut request, Utc.timestamp_opt(1700000000, 0).unwrap())?;
+        assert_eq!(request.headers()["cb-access-timestamp"], "1700000000");
+        assert_eq!(
+            request.headers()["cb-access-sign"],
+            "hErFx5RIHJL4RH77V4JRUeQctVhyNb+adc5h4GgL9wo="
+        );
+
+        let mut request = reqwest::Client::new()
+            .post("https://api.example.com/orders?product=BTC-USD")
+            .body(r#"{"size":"0.01"}"#)
+            .build()
+            .unwrap();
+        signer
+            .algorithm(HmacAlgorithm::Sha512)
+            .separator("\n")
+            .sign_at(&mut request, Utc.timestamp_opt(1700000000, 0).unwrap())?;
+        assert_eq!(
+            request.headers()["cb-access-sign"],
+            "+LoZnupHFle3tbJfCXNHvylWXKLfFJJ1NMkvHnPvCHaUWuOd2IvO6cU5p7Qe3iHNuodxsjkSR2aB/j8wjOoJ3g=="
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn every_attempt_gets_a_new_nonce() -> Result<()> {
+        let server = MockServer::start();
+        let nonces = Arc::new(Mutex::new(HashSet::new()));
+        let seen = nonces.clone();
+        let unavailable = server.mock(|when, then| {
+            when.method("POST")
+                .path("/orders")
+                .is_true(is_signed)
+                .is_true(move |request| {
+                    request
+                        .headers()
+                        .get("x-nonce")
+                        .and_then(|nonce| nonce.to_str().ok())
+                        .is_some_and(|nonce| seen.lock().unwrap().insert(nonce.to_string()))
+                });
+            then.status(503);
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .retry_policy(
+                RetryPolicy::new(3)
+                    .base_backoff(Duration::from_millis(10))
+                    .jitter(false)
+                    .retry_non_idempotent(true),
+            )
+            .signer(HmacSigner::new(SECRET).nonce(Placement::Header(String::from("X-Nonce"))))
+            .build();
+
+        let result = api
+            .post::<Value, Value>("/orders")?
+            .body(&json!({"size": "0.01"}))
+            .await;
+        assert!(result.is_err());
+        unavailable.assert_calls(3);
+        assert_eq!(nonces.lock().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn every_page_is_signed() -> Result<()> {
+        let server = MockServer::start();
+        let pages = server.mock(|when, then| {
+            when.method("GET")
+                .path("/trades")
+                .query_param("page[size]", "100")
+                .is_true(is_signed);
+            then.status(200)
+                .header("X-Total", "300")
+                .header("X-Per-Page", "100")
+                .json_body(json!([{"id": 1}]));
+        });
+        let api: Api = ApiBuilder::new(server.base_url())
+            .limit(100)
+            .signer(HmacSigner::new(SECRET))
+            .build()
+            .pagination(PaginationRule::Fixed(3));
+
+        let trades: Vec<Value> = api.get("/trades")?.await?;
+        assert_eq!(trades.len(), 3);
+        pages.assert_calls(3);
+        Ok(())
+    }
+}
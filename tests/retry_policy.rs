@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+#[cfg(test)]
+mod retry_policy_tests {
+    use reqt::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially() {
+        let policy = RetryPolicy::new(5)
+            .base_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(350))
+            .jitter(false);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(40), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn backoff_with_jitter_stays_in_range() {
+        let policy = RetryPolicy::new(5).base_backoff(Duration::from_millis(400));
+        for _ in 0..100 {
+            let backoff = policy.backoff(2);
+            assert!(backoff >= Duration::from_millis(400));
+            assert!(backoff <= Duration::from_millis(800));
+        }
+    }
+
+    #[test]
+    fn default_policy_does_not_retry() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.get_max_attempts(), 1);
+        assert_eq!(RetryPolicy::new(0).get_max_attempts(), 1);
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        let policy = RetryPolicy::new(3);
+        assert!(policy.is_retryable_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(policy.is_retryable_status(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!policy.is_retryable_status(reqwest::StatusCode::BAD_REQUEST));
+
+        let policy = policy.statuses([reqwest::StatusCode::INTERNAL_SERVER_ERROR]);
+        assert!(policy.is_retryable_status(reqwest::StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!policy.is_retryable_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+    }
+}